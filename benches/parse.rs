@@ -0,0 +1,112 @@
+// Times parsing a representative input with criterion, which compares each
+// run of `cargo bench` with the last, and prints how many allocations a parse
+// makes.
+
+use lang_tools::parser::Parser;
+use lang_tools::parser::basic_functions::{consume, matches};
+use lang_tools::parser::combinators::{chainl1, recursive, separated_by};
+use lang_tools::scanner::Token;
+use lang_tools::scanner::slice::SliceScanner;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Number,
+    Plus,
+    Star,
+    LeftParen,
+    RightParen,
+    Comma,
+    End
+}
+
+#[derive(Debug, Clone)]
+struct Tok(Kind, i64);
+
+impl Token for Tok {
+    type TokenType = Kind;
+    fn t_type(&self) -> Kind {
+        self.0
+    }
+}
+
+type BenchParser<T> = Parser<SliceScanner<Tok>, T>;
+type Op = Box<dyn Fn(i64, i64) -> i64>;
+
+fn number() -> BenchParser<i64> {
+    consume(Kind::Number, "expected number".to_string()).map(|token: Rc<Tok>| token.1)
+}
+
+fn op(kind: Kind, f: fn(i64, i64) -> i64) -> BenchParser<Op> {
+    consume(kind, "expected operator".to_string()).map(move |_| Box::new(f) as Op)
+}
+
+// expr = term ("+" term)*, term = atom ("*" atom)*, atom = number | "(" expr ")"
+fn expr() -> BenchParser<i64> {
+    recursive(|expr: Rc<dyn Fn() -> BenchParser<i64>>| {
+        let atom = move || matches(Kind::LeftParen).if_else(
+            expr().then_ignore(consume(Kind::RightParen, "expected )".to_string())),
+            number()
+        );
+        let term = move || chainl1(atom.clone(), || op(Kind::Star, |a, b| a.wrapping_mul(b)));
+        chainl1(term, || op(Kind::Plus, |a, b| a.wrapping_add(b)))
+    })
+}
+
+// A list of expressions like `1 + 2 * (3 + 4), ...`
+fn input(count: usize) -> SliceScanner<Tok> {
+    let mut tokens = vec![];
+    for i in 0..count {
+        if i > 0 {
+            tokens.push(Tok(Kind::Comma, 0));
+        }
+        tokens.extend(vec![
+            Tok(Kind::Number, 1), Tok(Kind::Plus, 0), Tok(Kind::Number, 2), Tok(Kind::Star, 0),
+            Tok(Kind::LeftParen, 0), Tok(Kind::Number, 3), Tok(Kind::Plus, 0), Tok(Kind::Number, 4),
+            Tok(Kind::RightParen, 0)
+        ]);
+    }
+    SliceScanner::new(tokens, Tok(Kind::End, 0))
+}
+
+fn parse_benchmark(c: &mut Criterion) {
+    let count = 2_000;
+    let parse = || {
+        let list = separated_by(expr, || consume(Kind::Comma, "expected ,".to_string()));
+        let values = list.run(input(count)).expect("bench input should parse");
+        assert_eq!(count, values.len());
+    };
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    parse();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!("{} expressions: {} allocations", count, allocations);
+
+    c.bench_function(&format!("parse {} expressions", count), |b| b.iter(parse));
+}
+
+criterion_group!(benches, parse_benchmark);
+criterion_main!(benches);
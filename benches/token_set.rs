@@ -0,0 +1,83 @@
+// Times `one_of`, which searches a list of token types for every token,
+// against `one_of_set`, which looks the type up in a `TokenSet`. This is a
+// plain harness run with `cargo bench`.
+
+use lang_tools::parser::basic_functions::{one_of, one_of_set};
+use lang_tools::parser::combinators::many;
+use lang_tools::scanner::{Token, TokenTypeIndex};
+use lang_tools::scanner::slice::SliceScanner;
+use lang_tools::scanner::token_set::TokenSet;
+
+use std::time::{Duration, Instant};
+
+const KINDS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Kind(usize);
+
+impl TokenTypeIndex for Kind {
+    const COUNT: usize = KINDS;
+    fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Tok(Kind);
+
+impl Token for Tok {
+    type TokenType = Kind;
+    fn t_type(&self) -> Kind {
+        self.0
+    }
+}
+
+// Every kind but the last is accepted, so the linear search has to look
+// through most of the list for most tokens
+fn accepted() -> Vec<Kind> {
+    (0..KINDS - 1).map(Kind).collect()
+}
+
+fn input(count: usize) -> SliceScanner<Tok> {
+    let tokens = (0..count).map(|i| Tok(Kind(i % (KINDS - 1)))).collect();
+    SliceScanner::new(tokens, Tok(Kind(KINDS - 1)))
+}
+
+// The fastest of several runs, which is the least disturbed by whatever else
+// the machine is doing
+fn fastest<F: Fn()>(run: F) -> Duration {
+    (0..30).map(|_| {
+        let start = Instant::now();
+        run();
+        start.elapsed()
+    }).min().unwrap()
+}
+
+fn main() {
+    let count = 20_000;
+    let linear = fastest(|| {
+        let types = accepted();
+        let tokens = many(move || one_of(types.clone())).run(input(count)).expect("bench input should parse");
+        assert_eq!(count, tokens.len());
+    });
+    let indexed = fastest(|| {
+        let set: TokenSet<Kind> = accepted().into_iter().collect();
+        let tokens = many(move || one_of_set(set.clone())).run(input(count)).expect("bench input should parse");
+        assert_eq!(count, tokens.len());
+    });
+    println!("{} tokens of {} kinds: linear {:?}, indexed {:?}", count, KINDS, linear, indexed);
+
+    // The lookups alone, without the parser around them
+    let kinds: Vec<Kind> = (0..count).map(|i| Kind(i % KINDS)).collect();
+    let types = accepted();
+    let set: TokenSet<Kind> = accepted().into_iter().collect();
+    let linear = fastest(|| {
+        let found = kinds.iter().filter(|kind| types.contains(std::hint::black_box(kind))).count();
+        assert!(found > 0);
+    });
+    let indexed = fastest(|| {
+        let found = kinds.iter().filter(|kind| set.contains(std::hint::black_box(kind))).count();
+        assert!(found > 0);
+    });
+    println!("{} lookups: linear {:?}, indexed {:?}", count, linear, indexed);
+}
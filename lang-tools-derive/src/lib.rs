@@ -0,0 +1,250 @@
+use proc_macro::{Delimiter, Group, TokenStream, TokenTree};
+
+use std::iter::Peekable;
+
+struct Variant {
+    name: String,
+    // The type given by `#[token(value = Type)]` and the pattern binding the
+    // variant's single field to `value`
+    value: Option<(String, String)>
+}
+
+// Derives `Token` for an enum. A `<Name>Type` enum with one fieldless
+// variant per token variant is generated as the token type, along with its
+// `TokenTypeIndex` impl. Variants marked `#[token(value = Type)]` must have
+// exactly one field, which becomes their payload: the enum then implements
+// `TokenValue` with a value of `Option<Type>`, which is `None` for unmarked
+// variants. Every marked variant must use the same type.
+#[proc_macro_derive(Token, attributes(token))]
+pub fn derive_token(input: TokenStream) -> TokenStream {
+    let code = match expand(input) {
+        Ok(code) => code,
+        Err(message) => format!("compile_error!({:?});", message)
+    };
+    code.parse().expect("generated code should be valid tokens")
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter().peekable();
+    let mut visibility = String::new();
+    loop {
+        match tokens.next() {
+            Some(TokenTree::Punct(ref punct)) if punct.as_char() == '#' => {
+                tokens.next();
+            }
+            Some(TokenTree::Ident(ref ident)) if ident.to_string() == "pub" => {
+                visibility = "pub".to_string();
+                if let Some(TokenTree::Group(group)) = tokens.peek() {
+                    if group.delimiter() == Delimiter::Parenthesis {
+                        visibility = format!("pub{}", group);
+                        tokens.next();
+                    }
+                }
+            }
+            Some(TokenTree::Ident(ref ident)) if ident.to_string() == "enum" => break,
+            Some(TokenTree::Ident(ref ident)) if ident.to_string() == "struct" || ident.to_string() == "union" => {
+                return Err("#[derive(Token)] only supports enums".to_string());
+            }
+            Some(_) => {}
+            None => return Err("expected an enum".to_string())
+        }
+    }
+
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err("expected the name of the enum".to_string())
+    };
+    let body = match tokens.next() {
+        Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Brace => group.stream(),
+        Some(TokenTree::Punct(ref punct)) if punct.as_char() == '<' => {
+            return Err("#[derive(Token)] doesn't support generic enums".to_string());
+        }
+        _ => return Err("expected the variants of the enum".to_string())
+    };
+    let variants = parse_variants(body)?;
+    if variants.is_empty() {
+        return Err("#[derive(Token)] needs at least one variant".to_string());
+    }
+
+    let type_name = format!("{}Type", name);
+    let mut code = format!(
+        "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n{} enum {} {{\n",
+        visibility, type_name
+    );
+    for variant in variants.iter() {
+        code += &format!("    {},\n", variant.name);
+    }
+    code += "}\n";
+
+    code += &format!("impl ::lang_tools::scanner::Token for {} {{\n", name);
+    code += &format!("    type TokenType = {};\n", type_name);
+    code += &format!("    fn t_type(&self) -> {} {{\n        match self {{\n", type_name);
+    for variant in variants.iter() {
+        code += &format!("            {}::{} {{ .. }} => {}::{},\n", name, variant.name, type_name, variant.name);
+    }
+    code += "        }\n    }\n}\n";
+
+    code += &format!("impl ::lang_tools::scanner::TokenTypeIndex for {} {{\n", type_name);
+    code += &format!("    const COUNT: usize = {};\n", variants.len());
+    code += "    fn index(&self) -> usize {\n        *self as usize\n    }\n}\n";
+
+    let values: Vec<_> = variants.iter()
+        .filter_map(|variant| variant.value.as_ref().map(|(value_type, pattern)| (variant, value_type, pattern)))
+        .collect();
+    if let Some((_, value_type, _)) = values.first() {
+        let same = |other: &String| other.replace(' ', "") == value_type.replace(' ', "");
+        if let Some((variant, _, _)) = values.iter().find(|(_, other, _)| !same(other)) {
+            return Err(format!("variant `{}` has a different value type; every #[token(value)] must use the same type", variant.name));
+        }
+
+        code += &format!("impl ::lang_tools::scanner::TokenValue for {} {{\n", name);
+        code += &format!("    type Value = ::std::option::Option<{}>;\n", value_type);
+        code += "    fn value(&self) -> Self::Value {\n        match self {\n";
+        for (variant, _, pattern) in values.iter() {
+            code += &format!(
+                "            {}::{}{} => ::std::option::Option::Some(::std::clone::Clone::clone(value)),\n",
+                name, variant.name, pattern
+            );
+        }
+        if values.len() < variants.len() {
+            code += "            _ => ::std::option::Option::None,\n";
+        }
+        code += "        }\n    }\n}\n";
+    }
+    Ok(code)
+}
+
+fn parse_variants(body: TokenStream) -> Result<Vec<Variant>, String> {
+    let mut variants = vec![];
+    for tokens in split_top_level(body) {
+        let mut tokens = tokens.into_iter().peekable();
+        let mut value_type = None;
+        while is_punct(tokens.peek(), '#') {
+            tokens.next();
+            if let Some(TokenTree::Group(attribute)) = tokens.next() {
+                if let Some(found) = parse_token_attribute(&attribute)? {
+                    value_type = Some(found);
+                }
+            }
+        }
+
+        let name = match tokens.next() {
+            Some(TokenTree::Ident(ident)) => ident.to_string(),
+            None => continue,
+            _ => return Err("expected a variant name".to_string())
+        };
+        let value = match value_type {
+            None => None,
+            Some(value_type) => Some((value_type, value_pattern(&name, tokens.peek())?))
+        };
+        variants.push(Variant { name, value });
+    }
+    Ok(variants)
+}
+
+// The value type from `#[token(value = Type)]`, or `None` for other attributes
+fn parse_token_attribute(attribute: &Group) -> Result<Option<String>, String> {
+    let mut tokens = attribute.stream().into_iter();
+    match tokens.next() {
+        Some(TokenTree::Ident(ref ident)) if ident.to_string() == "token" => {}
+        _ => return Ok(None)
+    }
+    let args = match tokens.next() {
+        Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Parenthesis => group.stream(),
+        _ => return Err("expected #[token(value = Type)]".to_string())
+    };
+
+    let mut args = args.into_iter();
+    match (args.next(), args.next()) {
+        (Some(TokenTree::Ident(ref key)), Some(TokenTree::Punct(ref eq)))
+        if key.to_string() == "value" && eq.as_char() == '=' => {
+            let value_type: TokenStream = args.collect();
+            if value_type.is_empty() {
+                Err("expected a type after `value =`".to_string())
+            } else {
+                Ok(Some(value_type.to_string()))
+            }
+        }
+        _ => Err("expected #[token(value = Type)]".to_string())
+    }
+}
+
+// A pattern binding the single field of a variant to `value`
+fn value_pattern(variant: &str, fields: Option<&TokenTree>) -> Result<String, String> {
+    let error = || format!("variant `{}` must have exactly one field to carry a value", variant);
+    let group = match fields {
+        Some(TokenTree::Group(group)) => group,
+        _ => return Err(error())
+    };
+    let mut fields = split_top_level(group.stream());
+    if fields.len() != 1 {
+        return Err(error());
+    }
+    match group.delimiter() {
+        Delimiter::Parenthesis => Ok("(value)".to_string()),
+        Delimiter::Brace => {
+            let field = fields.remove(0);
+            let mut tokens = field.into_iter().peekable();
+            // Skip attributes and visibility to reach the field name
+            loop {
+                match tokens.next() {
+                    Some(TokenTree::Punct(ref punct)) if punct.as_char() == '#' => {
+                        tokens.next();
+                    }
+                    Some(TokenTree::Ident(ref ident)) if ident.to_string() == "pub" => {
+                        skip_group(&mut tokens, Delimiter::Parenthesis);
+                    }
+                    Some(TokenTree::Ident(ident)) => return Ok(format!(" {{ {}: value }}", ident)),
+                    _ => return Err(error())
+                }
+            }
+        }
+        _ => Err(error())
+    }
+}
+
+fn skip_group<I: Iterator<Item = TokenTree>>(tokens: &mut Peekable<I>, delimiter: Delimiter) {
+    if let Some(TokenTree::Group(group)) = tokens.peek() {
+        if group.delimiter() == delimiter {
+            tokens.next();
+        }
+    }
+}
+
+fn is_punct(token: Option<&TokenTree>, ch: char) -> bool {
+    match token {
+        Some(TokenTree::Punct(punct)) => punct.as_char() == ch,
+        _ => false
+    }
+}
+
+// Splits a list such as variants or fields on the commas that aren't inside
+// a type's generic arguments
+fn split_top_level(stream: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut items = vec![];
+    let mut item = vec![];
+    let mut depth = 0usize;
+    let mut after_dash = false;
+    for token in stream {
+        let mut dash = false;
+        if let TokenTree::Punct(ref punct) = token {
+            match punct.as_char() {
+                ',' if depth == 0 => {
+                    items.push(std::mem::take(&mut item));
+                    continue;
+                }
+                '<' => depth += 1,
+                // `->` in a function type doesn't close anything
+                '>' if !after_dash => depth = depth.saturating_sub(1),
+                '-' => dash = true,
+                _ => {}
+            }
+        }
+        after_dash = dash;
+        item.push(token);
+    }
+    if !item.is_empty() {
+        items.push(item);
+    }
+    items
+}
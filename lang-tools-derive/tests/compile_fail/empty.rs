@@ -0,0 +1,6 @@
+use lang_tools_derive::Token;
+
+#[derive(Token)]
+enum Tok {}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use lang_tools_derive::Token;
+
+#[derive(Token)]
+enum Tok<T> {
+    Value(T),
+    End
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use lang_tools_derive::Token;
+
+#[derive(Token)]
+enum Tok {
+    #[token(value = String)]
+    Ident(String),
+    #[token(value = i64)]
+    Number(i64),
+    End
+}
+
+fn main() {}
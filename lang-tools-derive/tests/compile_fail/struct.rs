@@ -0,0 +1,8 @@
+use lang_tools_derive::Token;
+
+#[derive(Token)]
+struct Tok {
+    text: String
+}
+
+fn main() {}
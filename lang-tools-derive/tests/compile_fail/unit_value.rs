@@ -0,0 +1,10 @@
+use lang_tools_derive::Token;
+
+#[derive(Token)]
+enum Tok {
+    #[token(value = String)]
+    Ident,
+    End
+}
+
+fn main() {}
@@ -0,0 +1,57 @@
+use lang_tools::parser::basic_functions::{consume, token_value};
+use lang_tools::scanner::slice::SliceScanner;
+use lang_tools::scanner::{Token, TokenTypeIndex, TokenValue};
+use lang_tools_derive::Token;
+
+#[derive(Debug, Clone, PartialEq, Token)]
+pub enum Tok {
+    #[token(value = String)]
+    Ident(String),
+    #[token(value = String)]
+    Str { text: String },
+    Pair(char, char),
+    Plus,
+    End
+}
+
+#[derive(Debug, Clone, PartialEq, Token)]
+enum Punct {
+    Comma,
+    Semicolon
+}
+
+#[test]
+fn t_type_test() {
+    assert_eq!(TokType::Ident, Tok::Ident("x".to_string()).t_type());
+    assert_eq!(TokType::Str, Tok::Str { text: "x".to_string() }.t_type());
+    assert_eq!(TokType::Pair, Tok::Pair('a', 'b').t_type());
+    assert_eq!(TokType::Plus, Tok::Plus.t_type());
+    assert_eq!(PunctType::Comma, Punct::Comma.t_type());
+    assert_eq!(PunctType::Semicolon, Punct::Semicolon.t_type());
+}
+
+#[test]
+fn index_test() {
+    assert_eq!(5, TokType::COUNT);
+    assert_eq!(0, TokType::Ident.index());
+    assert_eq!(4, TokType::End.index());
+    assert_eq!(2, PunctType::COUNT);
+}
+
+#[test]
+fn value_test() {
+    assert_eq!(Some("x".to_string()), Tok::Ident("x".to_string()).value());
+    assert_eq!(Some("y".to_string()), Tok::Str { text: "y".to_string() }.value());
+    assert_eq!(None, Tok::Plus.value());
+
+    let scanner = SliceScanner::new(vec![Tok::Ident("a".to_string()), Tok::Plus], Tok::End);
+    let parser = token_value() >> |name|
+                 consume(TokType::Plus, "expected +".to_string()) >> move |_|
+                 lang_tools::parser::Parser::result(name);
+    assert_eq!(Ok(Some("a".to_string())), parser.run(scanner));
+}
+
+#[test]
+fn compile_fail_test() {
+    trybuild::TestCases::new().compile_fail("tests/compile_fail/*.rs");
+}
@@ -0,0 +1,327 @@
+use std::error::Error;
+use std::fmt;
+
+// Where in the source something happened. `line` and `column` start at 1,
+// and are 0 for scanners that only know how many tokens they have consumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize
+}
+
+impl Position {
+    pub fn new(offset: usize, line: usize, column: usize) -> Self {
+        Position { offset, line, column }
+    }
+
+    // The position of the byte `offset` into `source`, with its line and
+    // column. An offset inside a character is moved back to the start of it,
+    // and one past the end to the end.
+    pub fn locate(source: &str, offset: usize) -> Self {
+        let mut offset = offset.min(source.len());
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        Position::new(offset, line, column)
+    }
+}
+
+impl From<usize> for Position {
+    fn from(offset: usize) -> Self {
+        Position::new(offset, 0, 0)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "position {}", self.offset)
+        } else {
+            write!(f, "line {}, column {}", self.line, self.column)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    // Reported, but doesn't make the parse fail
+    Warning
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub position: Option<Position>,
+    // What the parser was looking for, such as "`)`" or "an expression"
+    pub expected: Option<String>,
+    // A stable identifier, such as "E0308", for documenting the error or
+    // letting users suppress it
+    pub code: Option<&'static str>,
+    // Related places to point out along with the error, such as where an
+    // unclosed delimiter was opened
+    pub notes: Vec<(Position, String)>,
+    // Set by `Parser::commit`: parsers chained after a fatal error are skipped
+    pub fatal: bool,
+    // Set by `Parser::cut`: alternatives aren't tried after a cut error
+    pub cut: bool,
+    pub severity: Severity
+}
+
+impl ParseError {
+    pub fn new<M: Into<String>>(message: M) -> Self {
+        ParseError {
+            message: message.into(),
+            position: None,
+            expected: None,
+            code: None,
+            notes: vec![],
+            fatal: false,
+            cut: false,
+            severity: Severity::Error
+        }
+    }
+
+    pub fn warning<M: Into<String>>(message: M) -> Self {
+        ParseError {
+            severity: Severity::Warning,
+            ..ParseError::new(message)
+        }
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.severity == Severity::Warning
+    }
+
+    pub fn at<P: Into<Position>>(mut self, position: P) -> Self {
+        self.position = Some(position.into());
+        self
+    }
+
+    pub fn expected<E: Into<String>>(mut self, expected: E) -> Self {
+        self.expected = Some(expected.into());
+        self
+    }
+
+    pub fn code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn note<P: Into<Position>, M: Into<String>>(mut self, position: P, message: M) -> Self {
+        self.notes.push((position.into(), message.into()));
+        self
+    }
+
+    pub fn fatal(mut self) -> Self {
+        self.fatal = true;
+        self
+    }
+
+    pub fn cut(mut self) -> Self {
+        self.cut = true;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.code, self.is_warning()) {
+            (Some(code), false) => write!(f, "error[{}]: ", code)?,
+            (Some(code), true) => write!(f, "warning[{}]: ", code)?,
+            (None, false) => (),
+            (None, true) => write!(f, "warning: ")?
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(expected) = &self.expected {
+            write!(f, " (expected {})", expected)?;
+        }
+        if let Some(position) = self.position {
+            write!(f, " at {}", position)?;
+        }
+        Ok(())
+    }
+}
+
+// The errors from a failed parse, displayed one per line
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseErrors(pub Vec<ParseError>);
+
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {}
+
+impl Error for ParseErrors {}
+
+impl From<Vec<ParseError>> for ParseErrors {
+    fn from(errors: Vec<ParseError>) -> Self {
+        ParseErrors(errors)
+    }
+}
+
+// Renders `error` followed by the line of `source` it points at, with a caret
+// under the column, and then each of its notes the same way, such as:
+//
+//     expected `)` at line 1, column 5
+//     1 | f(a b
+//       |     ^
+//     note: opened here at line 1, column 2
+//     1 | f(a b
+//       |  ^
+//
+// An error at the end of the input points just past the last character.
+// Errors only carry a single position, so a caret is all that can be shown.
+// Positions without a line are token indices rather than places in `source`,
+// so they are shown without an excerpt; a byte offset can be given a line
+// with `Position::locate` first.
+pub fn render_error(source: &str, error: &ParseError) -> String {
+    let mut rendered = error.to_string();
+    if let Some(position) = error.position {
+        rendered.push_str(&excerpt(source, position));
+    }
+    for (position, message) in &error.notes {
+        rendered.push_str(&format!("\nnote: {} at {}", message, position));
+        rendered.push_str(&excerpt(source, *position));
+    }
+    rendered
+}
+
+// The line of `source` at `position`, with a caret under the column
+fn excerpt(source: &str, position: Position) -> String {
+    let (line, column) = (position.line, position.column);
+    if line == 0 {
+        return String::new();
+    }
+    
+    let start: usize = source.split('\n').take(line - 1).map(|text| text.len() + 1).sum();
+    let text = source.split('\n').nth(line - 1).unwrap_or("").trim_end_matches('\r');
+    // The characters before the position are found by its offset where it can
+    // be, since a column may count a tab as more than one. Tabs are kept so
+    // that the caret lines up however they are displayed.
+    let before = position.offset.checked_sub(start)
+        .and_then(|length| text.get(..length))
+        .map_or(column.max(1) - 1, |before| before.chars().count());
+    let padding: String = text.chars()
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .chain(std::iter::repeat(' '))
+        .take(before)
+        .collect();
+    let gutter = line.to_string();
+    format!("\n{} | {}\n{} | {}^", gutter, text, " ".repeat(gutter.len()), padding)
+}
+
+// Whether any of `diagnostics` is an error rather than a warning
+pub(crate) fn has_errors(diagnostics: &[ParseError]) -> bool {
+    diagnostics.iter().any(|diagnostic| !diagnostic.is_warning())
+}
+
+// Whether any of `errors` rules out backtracking to another alternative
+pub(crate) fn is_cut(errors: &[ParseError]) -> bool {
+    errors.iter().any(|error| error.cut)
+}
+
+impl From<String> for ParseError {
+    fn from(message: String) -> Self {
+        ParseError::new(message)
+    }
+}
+
+impl From<&str> for ParseError {
+    fn from(message: &str) -> Self {
+        ParseError::new(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_test() {
+        assert_eq!("unexpected token", ParseError::new("unexpected token").to_string());
+        assert_eq!("unexpected token (expected `)`) at position 3",
+                   ParseError::new("unexpected token").expected("`)`").at(3).to_string());
+        assert_eq!("unexpected token at line 2, column 5",
+                   ParseError::new("unexpected token").at(Position::new(10, 2, 5)).to_string());
+        assert_eq!("warning: unused label at position 3", ParseError::warning("unused label").at(3).to_string());
+        assert_eq!("error[E0308]: mismatched types at position 3",
+                   ParseError::new("mismatched types").code("E0308").at(3).to_string());
+        assert_eq!("warning[W0001]: unused label", ParseError::warning("unused label").code("W0001").to_string());
+    }
+
+    #[test]
+    fn parse_errors_display_test() {
+        let errors = ParseErrors(vec![
+            ParseError::new("expected `)`").at(Position::new(4, 1, 5)),
+            ParseError::new("unexpected token").at(Position::new(12, 2, 3))
+        ]);
+        assert_eq!("expected `)` at line 1, column 5\nunexpected token at line 2, column 3", errors.to_string());
+
+        let boxed: Box<dyn Error> = Box::new(errors);
+        assert!(boxed.to_string().starts_with("expected `)`"));
+    }
+
+    #[test]
+    fn locate_test() {
+        let source = "é\nab";
+        assert_eq!(Position::new(3, 2, 1), Position::locate(source, 3));
+        assert_eq!(Position::new(5, 2, 3), Position::locate(source, 5));
+
+        // An offset inside `é` is moved to its start, and one past the end to
+        // the end
+        assert_eq!(Position::new(0, 1, 1), Position::locate(source, 1));
+        assert_eq!(Position::new(5, 2, 3), Position::locate(source, 50));
+    }
+
+    #[test]
+    fn render_error_test() {
+        let source = "let x = 1;\nf(a b\n";
+        let error = ParseError::new("expected `)`").at(Position::new(15, 2, 5));
+        assert_eq!("expected `)` at line 2, column 5\n2 | f(a b\n  |     ^", render_error(source, &error));
+
+        // A token index can't be shown in the source
+        let error = ParseError::new("expected `)`").at(15);
+        assert_eq!("expected `)` at position 15", render_error(source, &error));
+
+        // but a byte offset can, once it is located
+        let error = ParseError::new("expected `)`").at(Position::locate(source, 15));
+        assert_eq!("expected `)` at line 2, column 5\n2 | f(a b\n  |     ^", render_error(source, &error));
+        let error = ParseError::new("unexpected `é`").at(Position::locate("aé", 2));
+        assert_eq!("unexpected `é` at line 1, column 2\n1 | aé\n  |  ^", render_error("aé", &error));
+
+        let error = ParseError::new("unexpected end of input").at(Position::new(16, 2, 6));
+        assert_eq!("unexpected end of input at line 2, column 6\n2 | f(a b\n  |      ^", render_error(source, &error));
+
+        assert_eq!("no position", render_error(source, &ParseError::new("no position")));
+
+        // A column that counts a tab as several still has the caret under the
+        // character
+        let error = ParseError::new("unexpected `b`").at(Position::new(2, 1, 6));
+        assert_eq!("unexpected `b` at line 1, column 6\n1 | a\tb\n  |  \t^", render_error("a\tb", &error));
+
+        let error = ParseError::new("expected `)`")
+            .at(Position::new(15, 2, 5))
+            .note(Position::new(12, 2, 2), "opened here");
+        assert_eq!(
+            "expected `)` at line 2, column 5\n2 | f(a b\n  |     ^\nnote: opened here at line 2, column 2\n2 | f(a b\n  |  ^",
+            render_error(source, &error)
+        );
+
+        let error = ParseError::new("expected `)`").code("E0001").at(Position::new(15, 2, 5));
+        assert_eq!("error[E0001]: expected `)` at line 2, column 5\n2 | f(a b\n  |     ^", render_error(source, &error));
+    }
+}
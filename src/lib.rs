@@ -1,2 +1,3 @@
+pub mod error;
 pub mod parser;
 pub mod scanner;
\ No newline at end of file
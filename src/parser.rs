@@ -1,27 +1,180 @@
 pub mod basic_functions;
+pub mod chars;
+pub mod combinators;
+pub mod incremental;
+pub mod stream;
 
+use crate::error::{self, has_errors, is_cut, ParseError, ParseErrors};
 use crate::scanner;
-use std::ops::Shr;
+use std::any::Any;
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::{Add, BitOr, Not, Shr};
 
 pub struct Parser<S: scanner::Scanner, T: 'static> {
-    f: Box<dyn FnOnce(S) -> (T, S, Vec<String>)>
+    node: Node<S>,
+    value: PhantomData<T>,
+    // Set by `named`
+    name: Option<&'static str>
+}
+
+// A parser's value with its type erased
+type Value = Box<dyn Any>;
+
+// Parsers are stored without their value types so that `evaluate` can walk a
+// chain of binds in a loop, however long it is, rather than recursing into
+// each step. The most common parsers have their own variants rather than a
+// boxed function, to save an allocation each.
+enum Node<S> {
+    Primitive(Box<dyn FnOnce(S) -> (Value, S, Vec<ParseError>)>),
+    Bind(Box<dyn Bind<S>>),
+    // A value and errors, leaving the scanner as it is
+    Result(Value, Vec<ParseError>),
+    // A copy of the scanner
+    Scanner
+}
+
+// A node to run first and the continuation for its value, kept in a single
+// allocation
+trait Bind<S> {
+    // Takes out the node to run first. Only called once, before `then`.
+    fn first(&mut self) -> Node<S>;
+    fn then(self: Box<Self>, value: Value) -> Node<S>;
+}
+
+struct BindNode<S, T, F> {
+    first: Option<Node<S>>,
+    then: F,
+    value: PhantomData<T>
+}
+
+impl<S, T, U, F> Bind<S> for BindNode<S, T, F>
+where S: scanner::Scanner,
+      T: 'static,
+      U: 'static,
+      F: FnOnce(T) -> Parser<S, U> {
+    fn first(&mut self) -> Node<S> {
+        self.first.take().expect("a bind's first node is only taken once")
+    }
+    
+    fn then(self: Box<Self>, value: Value) -> Node<S> {
+        (self.then)(downcast(value)).node
+    }
+}
+
+// What's left to do once a node has produced its value
+enum Frame<S: scanner::Scanner> {
+    // Continue the chain with the value
+    Then(Box<dyn Bind<S>>),
+    // Put the errors of the earlier steps in front. If one of them was fatal,
+    // the scanner is restored to the checkpoint and the later errors dropped.
+    Combine(Vec<ParseError>, Option<scanner::Checkpoint<S>>)
+}
+
+fn evaluate_node<S>(mut node: Node<S>, mut scanner: S) -> (Value, S, Vec<ParseError>)
+where S: 'static + scanner::Scanner {
+    // The innermost continuation is kept out of `frames`, so the usual short
+    // chains never allocate the stack
+    let mut next: Option<Box<dyn Bind<S>>> = None;
+    let mut frames = vec![];
+    loop {
+        let (value, mut s, mut errors) = match node {
+            Node::Bind(mut bind) => {
+                node = bind.first();
+                if let Some(outer) = next.replace(bind) {
+                    frames.push(Frame::Then(outer));
+                }
+                continue;
+            }
+            Node::Primitive(f) => f(scanner),
+            Node::Result(value, errors) => (value, scanner, errors),
+            Node::Scanner => (Box::new(S::from_scanner(&scanner)) as Value, scanner, vec![])
+        };
+        
+        // Unwind until there's a continuation to run
+        let bind = loop {
+            let frame = match next.take() {
+                Some(bind) => Frame::Then(bind),
+                None => match frames.pop() {
+                    Some(frame) => frame,
+                    None => return (value, s, errors)
+                }
+            };
+            match frame {
+                Frame::Combine(earlier, Some(checkpoint)) => {
+                    s = s.restore(checkpoint);
+                    errors = earlier;
+                }
+                Frame::Combine(mut earlier, None) => {
+                    earlier.append(&mut errors);
+                    errors = earlier;
+                }
+                Frame::Then(bind) => break bind
+            }
+        };
+        
+        let checkpoint = if errors.iter().any(|error| error.fatal) {
+            Some(s.checkpoint())
+        } else {
+            None
+        };
+        // Skipping frames with nothing to do, and merging with the frame
+        // below, keeps long chains from piling up frames
+        match frames.last_mut() {
+            _ if errors.is_empty() => {}
+            Some(Frame::Combine(earlier, None)) if checkpoint.is_none() => earlier.append(&mut errors),
+            _ => frames.push(Frame::Combine(errors, checkpoint))
+        }
+        node = bind.then(value);
+        scanner = s;
+    }
+}
+
+fn downcast<T: 'static>(value: Value) -> T {
+    *value.downcast().expect("parser produced a value of the wrong type")
+}
+
+// Haskell-style do-notation over `>>`. Each line is either `name <- parser;`,
+// binding the parser's value, or `parser;`, discarding it. The block ends
+// with `return expr;` or with a final parser:
+//
+//     do_parse! {
+//         a <- advance();
+//         consume(TokenType::Comma, "expected ,".to_string());
+//         b <- advance();
+//         return (a, b);
+//     }
+//
+// expands to `advance() >> move |a| consume(..) >> move |_| advance() >>
+// move |b| Parser::result((a, b))`.
+#[macro_export]
+macro_rules! do_parse {
+    (return $value:expr $(;)?) => {
+        $crate::parser::Parser::result($value)
+    };
+    ($name:tt <- $parser:expr; $($rest:tt)+) => {
+        $parser >> move |$name| $crate::do_parse!($($rest)+)
+    };
+    ($parser:expr; $($rest:tt)+) => {
+        $parser >> move |_| $crate::do_parse!($($rest)+)
+    };
+    ($parser:expr $(;)?) => {
+        $parser
+    };
 }
 
 impl<S> Parser<S, S>
 where S: 'static + scanner::Scanner {
     pub fn get_scanner() -> Self {
-        Parser {
-            f: Box::new(|scanner| (S::from_scanner(&scanner), scanner, vec![]))
-        }
+        Parser::from_node(Node::Scanner)
     }
 }
 
 impl<S> Parser<S, ()>
 where S: 'static + scanner::Scanner {
     pub fn set_scanner(scanner: S) -> Self {
-        Parser {
-            f: Box::new(move |_| ((), S::from_scanner(&scanner), vec![]))
-        }
+        Parser::primitive(move |_| ((), S::from_scanner(&scanner), vec![]))
     }
 }
 
@@ -38,6 +191,13 @@ where S: 'static + scanner::Scanner {
         
     }
     
+    // Unlike `or`, both sides always run
+    pub fn and(self, other: Self) -> Self {
+        self >> |a|
+        other >> move |b|
+        Parser::result(a && b)
+    }
+    
     pub fn if_else<T>(self, t: Parser<S, T>, f: Parser<S, T>) -> Parser<S, T> {
         self >> |is_true|
             if is_true {
@@ -51,57 +211,402 @@ where S: 'static + scanner::Scanner {
 impl<S, T> Parser<S, T>
 where S: 'static + scanner::Scanner {
     pub fn result(value: T) -> Self {
-        Parser {
-            f: Box::new(move |scanner| (value, scanner, vec![]))
-        }
+        Parser::from_node(Node::Result(Box::new(value), vec![]))
+    }
+    
+    // Builds a parser from a function over the scanner. `f` is given the
+    // scanner as it is when the parser runs and must return the value, the
+    // scanner after whatever it consumed, and any errors (`String`s or
+    // `ParseError`s).
+    pub fn from_fn<E, F>(f: F) -> Self
+    where E: Into<ParseError>,
+          F: 'static + FnOnce(S) -> (T, S, Vec<E>) {
+        Parser::primitive(move |scanner| {
+            let (value, scanner, errors) = f(scanner);
+            (value, scanner, errors.into_iter().map(Into::into).collect())
+        })
     }
     
     pub fn error(value: T, error: String) -> Self {
-        Parser {
-            f: Box::new(move |scanner| (value, scanner, vec![error]))
+        Parser::fail(value, ParseError::new(error))
+    }
+    
+    // Like `error`, with a stable code such as "E0308" attached to the error
+    pub fn error_coded(value: T, code: &'static str, error: String) -> Self {
+        Parser::fail(value, ParseError::new(error).code(code))
+    }
+    
+    // Produces `value` along with a warning, which doesn't make the parse fail
+    pub fn warn(value: T, warning: String) -> Self {
+        Parser::fail(value, ParseError::warning(warning))
+    }
+    
+    pub fn fail(value: T, error: ParseError) -> Self {
+        Parser::from_node(Node::Result(Box::new(value), vec![error]))
+    }
+    
+    pub fn map<U, F>(self, f: F) -> Parser<S, U>
+    where F: 'static + FnOnce(T) -> U {
+        self >> move |value| Parser::result(f(value))
+    }
+    
+    // Checks the value this parser produces, adding an error built by
+    // `message` if `pred` rejects it. The value is returned either way.
+    pub fn verify<P, M>(self, pred: P, message: M) -> Parser<S, T>
+    where P: 'static + Fn(&T) -> bool,
+          M: 'static + Fn(&T) -> String {
+        self >> move |value|
+        if pred(&value) {
+            Parser::result(value)
+        } else {
+            let error = message(&value);
+            Parser::error(value, error)
+        }
+    }
+    
+    // Converts the value this parser produces with `f`. If the conversion
+    // fails its message is reported and `None` is produced instead, since a
+    // parser always yields a value.
+    pub fn filter_map<U, F>(self, f: F) -> Parser<S, Option<U>>
+    where U: 'static,
+          F: 'static + Fn(T) -> Result<U, String> {
+        self >> move |value|
+        match f(value) {
+            Ok(value) => Parser::result(Some(value)),
+            Err(message) => Parser::error(None, message)
         }
     }
     
-    pub fn run(self, scanner: S) -> Result<T, Vec<String>> {
-        let (value, _, errors) = self.evaluate(scanner);
+    // Rewrites the message of every error this parser produces
+    pub fn map_err<F>(self, f: F) -> Parser<S, T>
+    where F: 'static + Fn(String) -> String {
+        Parser::primitive(move |scanner| {
+            let (value, scanner, errors) = self.evaluate(scanner);
+            let errors = errors.into_iter()
+                .map(|error| ParseError { message: f(error.message), ..error })
+                .collect();
+            (value, scanner, errors)
+        })
+    }
+    
+    // Marks this parser's errors as coming after a decision point, so that
+    // `or_else`, `|`, `choice`, `optional` and the repetition combinators
+    // report them rather than backtracking to try something else. Put it
+    // around whatever follows the token that identifies a construct, such as
+    // the condition after `if`.
+    pub fn cut(self) -> Parser<S, T> {
+        Parser::primitive(move |scanner| {
+            let (value, scanner, errors) = self.evaluate(scanner);
+            (value, scanner, errors.into_iter().map(ParseError::cut).collect())
+        })
+    }
+    
+    // Makes this parser's errors fatal, so that everything chained after it
+    // with `>>` is skipped and only the errors up to this point are reported.
+    // Use it where carrying on would only produce cascading errors.
+    pub fn commit(self) -> Parser<S, T> {
+        Parser::primitive(move |scanner| {
+            let (value, scanner, errors) = self.evaluate(scanner);
+            (value, scanner, errors.into_iter().map(ParseError::fatal).collect())
+        })
+    }
+    
+    // Calls `f` on the value this parser produces, for logging while
+    // developing a grammar. The value, scanner and errors are left as they are.
+    pub fn inspect<F>(self, f: F) -> Parser<S, T>
+    where F: 'static + Fn(&T) {
+        self.map(move |value| {
+            f(&value);
+            value
+        })
+    }
+    
+    // Prints `label` along with any name given by `named`, whether this
+    // parser matched, and where it started and ended to stderr each time it
+    // runs. Like `inspect`, it doesn't change the parse.
+    pub fn trace<L: Into<String>>(self, label: L) -> Parser<S, T> {
+        let label = match self.name {
+            Some(name) => format!("{} ({})", label.into(), name),
+            None => label.into()
+        };
+        Parser::primitive(move |scanner: S| {
+            let start: error::Position = scanner.position().into();
+            let (value, scanner, errors) = self.evaluate(scanner);
+            let end: error::Position = scanner.position().into();
+            let outcome = if has_errors(&errors) { "failed" } else { "matched" };
+            eprintln!("{}: {} from {} to {}", label, outcome, start, end);
+            (value, scanner, errors)
+        })
+    }
+    
+    // Gives this parser a name for diagnostics. Errors it produces that
+    // don't already say what was expected are marked as expecting `name`, and
+    // `trace` prints it.
+    pub fn named(self, name: &'static str) -> Parser<S, T> {
+        let parser = Parser::primitive(move |scanner| {
+            let (value, scanner, errors) = self.evaluate(scanner);
+            let errors = errors.into_iter()
+                .map(|error| if error.expected.is_none() && !error.is_warning() {
+                    error.expected(name)
+                } else {
+                    error
+                })
+                .collect();
+            (value, scanner, errors)
+        });
+        Parser { name: Some(name), ..parser }
+    }
+    
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+    
+    // Prefixes every error this parser produces with `context`, such as
+    // "while parsing if-statement"
+    pub fn label<C: Into<String>>(self, context: C) -> Parser<S, T> {
+        let context = context.into();
+        self.map_err(move |message| format!("{}: {}", context, message))
+    }
+    
+    // Runs this parser, yielding `None` without any errors if it fails. The
+    // scanner is restored to where it was before the attempt on failure,
+    // unless the failure came after a `cut`, whose errors are kept.
+    pub fn optional(self) -> Parser<S, Option<T>> {
+        Parser::primitive(move |scanner: S| {
+            let checkpoint = scanner.checkpoint();
+            let (value, scanner, errors) = self.evaluate(scanner);
+            if !has_errors(&errors) || is_cut(&errors) {
+                (Some(value), scanner, errors)
+            } else {
+                (None, scanner.restore(checkpoint), vec![])
+            }
+        })
+    }
+    
+    // Like `optional`, but yields `default` when this parser fails, as for a
+    // visibility modifier that defaults to private
+    pub fn or_default(self, default: T) -> Parser<S, T> {
+        self.optional().map(move |value| value.unwrap_or(default))
+    }
+    
+    // Like `or_default`, with `T::default()` as the fallback
+    pub fn opt_with_default(self) -> Parser<S, T>
+    where T: Default {
+        self.optional().map(Option::unwrap_or_default)
+    }
+    
+    // Runs this parser, putting the scanner back to where it started if it
+    // fails. The errors are kept, so the failure is still reported, but a
+    // failed attempt never consumes any input.
+    pub fn attempt(self) -> Parser<S, T> {
+        Parser::primitive(move |scanner: S| {
+            let checkpoint = scanner.checkpoint();
+            let (value, scanner, errors) = self.evaluate(scanner);
+            if !has_errors(&errors) {
+                (value, scanner, errors)
+            } else {
+                (value, scanner.restore(checkpoint), errors)
+            }
+        })
+    }
+    
+    // Runs this parser without consuming anything: the scanner is put back
+    // afterwards whether or not it succeeded. The value and errors are kept.
+    pub fn lookahead(self) -> Parser<S, T> {
+        Parser::primitive(move |scanner: S| {
+            let checkpoint = scanner.checkpoint();
+            let (value, scanner, errors) = self.evaluate(scanner);
+            (value, scanner.restore(checkpoint), errors)
+        })
+    }
+    
+    // Runs `other` from where this parser started if this parser fails,
+    // discarding this parser's errors and any input it consumed. A failure
+    // after a `cut` is returned as is instead.
+    pub fn or_else(self, other: Parser<S, T>) -> Parser<S, T> {
+        Parser::primitive(move |scanner: S| {
+            let checkpoint = scanner.checkpoint();
+            let (value, scanner, errors) = self.evaluate(scanner);
+            if !has_errors(&errors) || is_cut(&errors) {
+                (value, scanner, errors)
+            } else {
+                other.evaluate(scanner.restore(checkpoint))
+            }
+        })
+    }
+    
+    // If this parser fails, keeps its errors but runs `fallback` from where it
+    // stopped to produce a value, so parsing can carry on and report later
+    // errors too
+    pub fn recover_with<F>(self, fallback: F) -> Parser<S, T>
+    where F: 'static + FnOnce() -> Parser<S, T> {
+        Parser::primitive(move |scanner: S| {
+            let (value, scanner, mut errors) = self.evaluate(scanner);
+            if !has_errors(&errors) {
+                (value, scanner, errors)
+            } else {
+                let (value, scanner, mut fallback_errors) = fallback().evaluate(scanner);
+                errors.append(&mut fallback_errors);
+                (value, scanner, errors)
+            }
+        })
+    }
+    
+    // Runs this parser then `next`, keeping only `next`'s value
+    pub fn ignore_then<U>(self, next: Parser<S, U>) -> Parser<S, U> {
+        self >> |_| next
+    }
+    
+    // Runs this parser then `next`, keeping only this parser's value
+    pub fn then_ignore<U>(self, next: Parser<S, U>) -> Parser<S, T> {
+        self >> |value|
+        next >> move |_|
+        Parser::result(value)
+    }
+    
+    // Continues with a parser chosen from this parser's value. This is the
+    // same as `>>`, named for grammars where the rest of the parse depends on
+    // what was just parsed.
+    pub fn try_map<U, F>(self, f: F) -> Parser<S, U>
+    where F: 'static + FnOnce(T) -> Parser<S, U> {
+        self >> f
+    }
+    
+    // Runs this parser and then requires the input to be exhausted. Use this
+    // at the top level so that a parser can't succeed while leaving a suffix
+    // unparsed.
+    pub fn parse_to_end(self) -> Parser<S, T> {
+        self.then_ignore(basic_functions::eof())
+    }
+    
+    // Succeeds unless there are errors; warnings alone are dropped. Use
+    // `run_with_diagnostics` to see them.
+    pub fn run(self, scanner: S) -> Result<T, ParseErrors> {
+        self.run_with_diagnostics(scanner).0
+    }
+    
+    // Like `run`, but also returns the warnings
+    pub fn run_with_diagnostics(self, scanner: S) -> (Result<T, ParseErrors>, Vec<ParseError>) {
+        let (value, _, diagnostics) = self.evaluate(scanner);
+        let (warnings, errors): (Vec<_>, Vec<_>) = diagnostics.into_iter().partition(ParseError::is_warning);
         if errors.is_empty() {
-            Ok(value)
+            (Ok(value), warnings)
         } else {
-            Err(errors)
+            (Err(ParseErrors(errors)), warnings)
         }
     }
     
-    fn evaluate(self, scanner: S) -> (T, S, Vec<String>) {
-        (self.f)(scanner)
+    // Like `run`, but also returns the scanner and the value when there are
+    // errors, so callers can see how far parsing got and keep partial results
+    pub fn run_partial(self, scanner: S) -> (T, S, Vec<ParseError>) {
+        self.evaluate(scanner)
+    }
+    
+    fn evaluate(self, scanner: S) -> (T, S, Vec<ParseError>) {
+        let (value, scanner, errors) = evaluate_node(self.node, scanner);
+        (downcast(value), scanner, errors)
+    }
+    
+    fn primitive<F>(f: F) -> Self
+    where F: 'static + FnOnce(S) -> (T, S, Vec<ParseError>) {
+        Parser::from_node(Node::Primitive(Box::new(move |scanner| {
+            let (value, scanner, errors) = f(scanner);
+            (Box::new(value) as Value, scanner, errors)
+        })))
+    }
+    
+    fn from_node(node: Node<S>) -> Self {
+        Parser { node, value: PhantomData, name: None }
     }
 }
 
-// Mimicking Haskell's >>= operator
-// Later, code macro for this instead?
+// Mimicking Haskell's >>= operator. See `do_parse!` for a flatter way to
+// write long chains.
 impl<S, T, U: 'static, V: 'static> Shr<V> for Parser<S, T> 
 where V: FnOnce(T) -> Parser<S, U>,
       S: 'static + scanner::Scanner {
     type Output = Parser<S, U>;
     
+    // The rest of the chain still runs after a fatal error, since it has to
+    // produce a value, but nothing it consumes or reports is kept
     fn shr(self, f: V) -> Parser<S, U> {
-        Parser {
-            f: Box::new(move |scanner| {
-                let (value, scanner, mut errors) = self.evaluate(scanner);
-                let value = f(value);
-                let (v, s, mut other_errors) = value.evaluate(scanner);
-                errors.append(&mut other_errors);
-                (v, s, errors)
-            })
-        }
+        Parser::from_node(Node::Bind(Box::new(BindNode { first: Some(self.node), then: f, value: PhantomData::<T> })))
+    }
+}
+
+// Sequencing that keeps both values: `p + q` runs `p` then `q` and yields
+// `(p's value, q's value)`, keeping the errors of both like `>>`
+impl<S, T, U: 'static> Add<Parser<S, U>> for Parser<S, T>
+where S: 'static + scanner::Scanner {
+    type Output = Parser<S, (T, U)>;
+    
+    fn add(self, other: Parser<S, U>) -> Parser<S, (T, U)> {
+        self.try_map(|a| other.map(move |b| (a, b)))
+    }
+}
+
+// Ordered alternation: `p | q` is `p.or_else(q)`
+impl<S, T> BitOr for Parser<S, T>
+where S: 'static + scanner::Scanner {
+    type Output = Self;
+    
+    fn bitor(self, other: Self) -> Self {
+        self.or_else(other)
+    }
+}
+
+impl<S> Not for Parser<S, bool>
+where S: 'static + scanner::Scanner {
+    type Output = Self;
+    
+    fn not(self) -> Self {
+        self >> |a|
+        Parser::result(!a)
     }
 }
 
-pub fn multi_if<S, T>(mut branches: Vec<(Parser<S, bool>, Parser<S, T>)>, otherwise: Parser<S, T>)
--> Parser<S, T>
+// Tries `strictest` and then each of `fallbacks` in order, from strictest to
+// most lenient, restarting from the same scanner each time, and commits to the
+// first one that parses without errors. Accepting anything but `strictest`
+// produces a warning naming the fallback level that matched. If every grammar
+// fails, the last attempt's value and errors are returned.
+pub fn fallback_chain<S, T>(strictest: Parser<S, T>, fallbacks: Vec<Parser<S, T>>) -> Parser<S, (T, Option<String>)>
 where S: 'static + scanner::Scanner {
-    match branches.pop() {
-        None => otherwise,
-        Some((cond, t)) => multi_if(branches, cond.if_else(t, otherwise))
+    Parser::primitive(move |mut scanner: S| {
+        let count = fallbacks.len() + 1;
+        for (level, grammar) in std::iter::once(strictest).chain(fallbacks).enumerate() {
+            let checkpoint = scanner.checkpoint();
+            let (value, s, errors) = grammar.evaluate(scanner);
+            if !has_errors(&errors) {
+                let warning = if level == 0 {
+                    None
+                } else {
+                    Some(format!("accepted using fallback level {}", level))
+                };
+                return ((value, warning), s, errors);
+            } else if level + 1 == count {
+                return ((value, None), s, errors);
+            }
+            scanner = s.restore(checkpoint);
+        }
+        unreachable!("the last grammar always returns")
+    })
+}
+
+// Parses a list and reports an error, built by `dup_msg`, for every item whose
+// key has already been seen earlier in the list
+pub fn unique_by<S, T, K, F, M>(list: Parser<S, Vec<T>>, key_fn: F, dup_msg: M) -> Parser<S, Vec<T>>
+where S: 'static + scanner::Scanner,
+      K: Eq + Hash,
+      F: 'static + Fn(&T) -> K,
+      M: 'static + Fn(&T) -> String {
+    list >> move |items: Vec<T>| {
+        let mut seen = HashSet::new();
+        let errors = items.iter()
+            .filter(|item| !seen.insert(key_fn(item)))
+            .map(|item| ParseError::new(dup_msg(item)))
+            .collect();
+        Parser::primitive(move |scanner| (items, scanner, errors))
     }
 }
 
@@ -128,6 +633,26 @@ mod tests {
         assert_eq!(Ok(token), parser.run(TestScanner::new("a")));
     }
     
+    #[test]
+    fn long_chain_test() {
+        // Binds are evaluated in a loop, so a long chain grows the heap
+        // rather than the stack, whether it's built up front or as it runs
+        let mut parser = TestParser::result(0);
+        for _ in 0..100_000 {
+            parser = parser >> |n| TestParser::result(n + 1);
+        }
+        assert_eq!(Ok(100_000), parser.run(TestScanner::new("")));
+        
+        fn sum_to(n: u64) -> TestParser<u64> {
+            if n == 0 {
+                TestParser::result(0)
+            } else {
+                TestParser::result(n) >> move |n| sum_to(n - 1).map(move |sum| sum + n)
+            }
+        }
+        assert_eq!(Ok(5_000_050_000), sum_to(100_000).run(TestScanner::new("")));
+    }
+    
     #[test]
     fn if_else_test() {
         let parser = TestParser::result(true).if_else(
@@ -159,29 +684,353 @@ mod tests {
     }
     
     #[test]
-    fn multi_if_test() {
-        let parser = multi_if(vec![
-            (TestParser::result(false), TestParser::result("fail 1")),
-            (TestParser::result(true), TestParser::result("success"))
-        ], TestParser::result("fail 2"));
+    fn or_else_test() {
+        let parser = (TestParser::set_scanner(TestScanner::new("consumed")) >> |_|
+                     TestParser::error(TestScanner::new(""), "failed".to_string()))
+                     .or_else(TestParser::get_scanner());
+        assert_eq!(Ok(TestScanner::new("original")), parser.run(TestScanner::new("original")));
         
-        assert_eq!(Ok("success"), parser.run(TestScanner::new("")));
+        let parser = TestParser::result("left").or_else(TestParser::error("right", "not run".to_string()));
+        assert_eq!(Ok("left"), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::error("left", "left".to_string())
+                     .or_else(TestParser::error("right", "right".to_string()));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("right")])), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn bitor_test() {
+        let parser = (TestParser::set_scanner(TestScanner::new("consumed")) >> |_|
+                     TestParser::error(TestScanner::new(""), "failed".to_string()))
+                     | TestParser::get_scanner();
+        assert_eq!(Ok(TestScanner::new("original")), parser.run(TestScanner::new("original")));
+        
+        let parser = TestParser::result("left") | TestParser::error("right", "not run".to_string());
+        assert_eq!(Ok("left"), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn add_test() {
+        let parser = TestParser::result(1) + TestParser::result("x");
+        assert_eq!(Ok((1, "x")), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::error(1, "left".to_string()) + TestParser::result("x");
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("left")])), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::error(1, "left".to_string()) + TestParser::error("x", "right".to_string());
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("left"), ParseError::new("right")])), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn do_parse_test() {
+        let parser: TestParser<_> = do_parse! {
+            a <- TestParser::result(1);
+            TestParser::set_scanner(TestScanner::new("a"));
+            b <- TestParser::result(a + 1);
+            return (a, b);
+        };
+        assert_eq!(((1, 2), TestScanner::new("a"), vec![]), parser.evaluate(TestScanner::new("")));
+        
+        let parser: TestParser<_> = do_parse! {
+            _ <- TestParser::error(1, "first".to_string());
+            TestParser::error((), "second".to_string())
+        };
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("first"), ParseError::new("second")])), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn and_test() {
+        let parser = TestParser::result(false).and(TestParser::result(false));
+        assert!(!parser.run(TestScanner::new("")).unwrap());
+        
+        let parser = TestParser::result(true).and(TestParser::result(false));
+        assert!(!parser.run(TestScanner::new("")).unwrap());
+        
+        let parser = TestParser::result(false).and(TestParser::result(true));
+        assert!(!parser.run(TestScanner::new("")).unwrap());
+        
+        let parser = TestParser::result(true).and(TestParser::result(true));
+        assert!(parser.run(TestScanner::new("")).unwrap());
+        
+        let parser = TestParser::error(false, "left".to_string()).and(TestParser::error(true, "right".to_string()));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("left"), ParseError::new("right")])), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn not_test() {
+        let parser = !TestParser::result(true);
+        assert!(!parser.run(TestScanner::new("")).unwrap());
+        
+        let parser = TestParser::result(false).not();
+        assert!(parser.run(TestScanner::new("")).unwrap());
+        
+        let parser = (TestParser::set_scanner(TestScanner::new("a")) >> |_|
+                     TestParser::error(true, "kept".to_string())).not();
+        assert_eq!((false, TestScanner::new("a"), vec![ParseError::new("kept")]), parser.evaluate(TestScanner::new("")));
     }
     
     #[test]
     fn error() {
         let parser = TestParser::error((), "success".to_string());
-        assert_eq!(Err(vec!["success".to_string()]), parser.run(TestScanner::new("")));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("success")])), parser.run(TestScanner::new("")));
         
         let parser = TestParser::error((), "success".to_string()) >> |_|
                      TestParser::result("failed");
-        assert_eq!(Err(vec!["success".to_string()]), parser.run(TestScanner::new("")));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("success")])), parser.run(TestScanner::new("")));
         
         let parser = TestParser::error((), "success 1".to_string()) >> |_|
                      TestParser::result("ignored") >> |_|
                      TestParser::error((), "success 2".to_string());
-        assert_eq!(Err(vec!["success 1".to_string(), "success 2".to_string()]),
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("success 1"), ParseError::new("success 2")])),
+                   parser.run(TestScanner::new("")));
+        
+        let error = ParseError::new("unexpected token").expected("`)`").at(2);
+        let parser = TestParser::fail((), error.clone());
+        assert_eq!(Err(ParseErrors(vec![error])), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn commit_test() {
+        let parser = TestParser::error((), "first".to_string()).commit() >> |_|
+                     TestParser::set_scanner(TestScanner::new("consumed")) >> |_|
+                     TestParser::error((), "cascading".to_string());
+        assert_eq!(((), TestScanner::new("original"), vec![ParseError::new("first").fatal()]),
+                   parser.evaluate(TestScanner::new("original")));
+        
+        let parser = TestParser::result(1).commit() >> |_|
+                     TestParser::error((), "second".to_string());
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("second")])), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn warning_test() {
+        let parser = TestParser::warn(1, "unused value".to_string()) >> |value|
+                     TestParser::result(value + 1);
+        assert_eq!((Ok(2), vec![ParseError::warning("unused value")]), parser.run_with_diagnostics(TestScanner::new("")));
+        
+        let parser = TestParser::warn(1, "unused value".to_string()) >> |_|
+                     TestParser::error((), "bad".to_string());
+        assert_eq!((Err(ParseErrors(vec![ParseError::new("bad")])), vec![ParseError::warning("unused value")]),
+                   parser.run_with_diagnostics(TestScanner::new("")));
+        
+        // Warnings don't count as failures when backtracking
+        let parser = TestParser::warn(1, "unused value".to_string()).or_else(TestParser::result(2));
+        assert_eq!(Ok(1), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn error_coded_test() {
+        let parser = TestParser::error_coded((), "E0001", "bad".to_string());
+        let errors = parser.run(TestScanner::new("")).unwrap_err();
+        assert_eq!(ParseErrors(vec![ParseError::new("bad").code("E0001")]), errors);
+        assert_eq!(Some("E0001"), errors.0[0].code);
+        assert_eq!("error[E0001]: bad", errors.to_string());
+    }
+    
+    #[test]
+    fn fallback_chain_test() {
+        let parser = fallback_chain(TestParser::result("strict"), vec![
+            TestParser::result("lenient")
+        ]);
+        assert_eq!(Ok(("strict", None)), parser.run(TestScanner::new("")));
+        
+        let parser = fallback_chain(TestParser::error("strict", "not strict".to_string()), vec![
+            TestParser::error("lenient 1", "not lenient".to_string()),
+            TestParser::result("lenient 2")
+        ]);
+        assert_eq!(Ok(("lenient 2", Some("accepted using fallback level 2".to_string()))),
                    parser.run(TestScanner::new("")));
+        
+        let parser = fallback_chain(TestParser::error("strict", "not strict".to_string()), vec![
+            TestParser::error("lenient", "not lenient".to_string())
+        ]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("not lenient")])), parser.run(TestScanner::new("")));
+        
+        // Each grammar starts from the original scanner
+        let parser = fallback_chain(
+            TestParser::set_scanner(TestScanner::new("consumed")) >> |_|
+            TestParser::get_scanner() >> |scanner|
+            TestParser::error(scanner, "not strict".to_string()),
+            vec![TestParser::get_scanner()]
+        );
+        assert_eq!(Ok((TestScanner::new("original"), Some("accepted using fallback level 1".to_string()))),
+                   parser.run(TestScanner::new("original")));
+        
+        let parser = fallback_chain(TestParser::error("strict", "not strict".to_string()), vec![]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("not strict")])), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn map_test() {
+        let parser = TestParser::result(2).map(|x| x + 1);
+        assert_eq!(Ok(3), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::error(2, "failed".to_string()).map(|x| x + 1);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("failed")])), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::set_scanner(TestScanner::new("a")).map(|_| "mapped");
+        assert_eq!(("mapped", TestScanner::new("a"), vec![]), parser.evaluate(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn verify_test() {
+        let positive = |parser: TestParser<i32>| parser.verify(|&n| n > 0, |n| format!("{} is not positive", n));
+        assert_eq!(Ok(3), positive(TestParser::result(3)).run(TestScanner::new("")));
+        assert_eq!((-3, TestScanner::new(""), vec![ParseError::new("-3 is not positive")]),
+                   positive(TestParser::result(-3)).evaluate(TestScanner::new("")));
+        
+        let parser = positive(TestParser::error(-3, "bad literal".to_string()));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("bad literal"), ParseError::new("-3 is not positive")])),
+                   parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn filter_map_test() {
+        let float = |literal: &'static str| TestParser::result(literal)
+            .filter_map(|literal| literal.parse::<f64>().map_err(|_| format!("invalid number `{}`", literal)));
+        assert_eq!(Ok(Some(1.5)), float("1.5").run(TestScanner::new("")));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("invalid number `x`")])), float("x").run(TestScanner::new("")));
+        let (value, _, _) = float("x").evaluate(TestScanner::new(""));
+        assert_eq!(None, value);
+        
+        // The error is kept through later steps
+        let parser = float("x") >> |value: Option<f64>|
+                     TestParser::result(value.map(|value| value + 1.0)) >> |_|
+                     TestParser::error((), "later".to_string());
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("invalid number `x`"), ParseError::new("later")])),
+                   parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn map_err_test() {
+        let parser = TestParser::error((), "bad".to_string()).map_err(|e| format!("error: {}", e));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("error: bad")])), parser.run(TestScanner::new("")));
+        
+        let parser = (TestParser::error((), "first".to_string()) >> |_|
+                     TestParser::error((), "second".to_string()))
+                     .map_err(|e| format!("in function body: {}", e));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("in function body: first"), ParseError::new("in function body: second")])),
+                   parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::result(1).map_err(|_| panic!("no errors to map"));
+        assert_eq!(Ok(1), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn inspect_test() {
+        let seen = Rc::new(std::cell::RefCell::new(vec![]));
+        let log = Rc::clone(&seen);
+        let parser = (TestParser::result(1) >> |n| TestParser::error(n + 1, "later".to_string()))
+                     .inspect(move |value| log.borrow_mut().push(*value));
+        assert_eq!((2, TestScanner::new("a"), vec![ParseError::new("later")]), parser.evaluate(TestScanner::new("a")));
+        assert_eq!(vec![2], *seen.borrow());
+        
+        let parser = TestParser::result("value").trace("value");
+        assert_eq!(Ok("value"), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn named_test() {
+        let parser = TestParser::error(0, "unexpected token".to_string()).named("number");
+        assert_eq!(Some("number"), parser.name());
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("unexpected token").expected("number")])),
+                   parser.run(TestScanner::new("")));
+        
+        // Errors that already say what was expected are left alone
+        let parser = TestParser::fail(0, ParseError::new("unexpected token").expected("digit")).named("number");
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("unexpected token").expected("digit")])),
+                   parser.run(TestScanner::new("")));
+        
+        assert_eq!(None, TestParser::result(0).named("number").map(|n| n + 1).name());
+    }
+    
+    #[test]
+    fn label_test() {
+        let parser = TestParser::result(1).label("while parsing if-statement");
+        assert_eq!(Ok(1), parser.run(TestScanner::new("")));
+        
+        let parser = (TestParser::error((), "expected condition".to_string()) >> |_|
+                     TestParser::error((), "expected `then`".to_string()))
+                     .label("while parsing if-statement");
+        assert_eq!(Err(ParseErrors(vec![
+            ParseError::new("while parsing if-statement: expected condition"),
+            ParseError::new("while parsing if-statement: expected `then`")
+        ])), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn optional_test() {
+        let parser = TestParser::result(1).optional();
+        assert_eq!(Ok(Some(1)), parser.run(TestScanner::new("")));
+        
+        let parser = (TestParser::set_scanner(TestScanner::new("consumed")) >> |_|
+                     TestParser::error(1, "failed".to_string())).optional();
+        assert_eq!((None, TestScanner::new("original"), vec![]), parser.evaluate(TestScanner::new("original")));
+        
+        let parser = (TestParser::set_scanner(TestScanner::new("consumed")) >> |_|
+                     TestParser::result(1)).optional();
+        assert_eq!((Some(1), TestScanner::new("consumed"), vec![]), parser.evaluate(TestScanner::new("original")));
+    }
+    
+    #[test]
+    fn or_default_test() {
+        let parser = TestParser::result("public").or_default("private");
+        assert_eq!(Ok("public"), parser.run(TestScanner::new("")));
+        
+        let parser = (TestParser::set_scanner(TestScanner::new("consumed")) >> |_|
+                     TestParser::error("public", "expected modifier".to_string())).or_default("private");
+        assert_eq!(("private", TestScanner::new("original"), vec![]), parser.evaluate(TestScanner::new("original")));
+        
+        let parser = TestParser::error(5, "failed".to_string()).opt_with_default();
+        assert_eq!(Ok(0), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn ignore_then_test() {
+        let parser = TestParser::result("(").ignore_then(TestParser::result("expr"));
+        assert_eq!(Ok("expr"), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::result("expr").then_ignore(TestParser::result(")"));
+        assert_eq!(Ok("expr"), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::error("(", "left".to_string()).ignore_then(TestParser::error((), "right".to_string()));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("left"), ParseError::new("right")])), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::error("(", "left".to_string()).then_ignore(TestParser::error((), "right".to_string()));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("left"), ParseError::new("right")])), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::set_scanner(TestScanner::new("a"))
+                     .then_ignore(TestParser::get_scanner())
+                     .ignore_then(TestParser::get_scanner());
+        assert_eq!(Ok(TestScanner::new("a")), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn try_map_test() {
+        let suffix = |is_array| if is_array {
+            TestParser::result("[]")
+        } else {
+            TestParser::result("")
+        };
+        
+        let parser = TestParser::result(true).try_map(suffix);
+        assert_eq!(Ok("[]"), parser.run(TestScanner::new("")));
+        
+        let parser = TestParser::result(false).try_map(suffix);
+        assert_eq!(Ok(""), parser.run(TestScanner::new("")));
+    }
+    
+    #[test]
+    fn unique_by_test() {
+        let fields = |names: Vec<&'static str>| unique_by(
+            TestParser::result(names.into_iter().map(|name| (name, 0)).collect()),
+            |field: &(&str, i32)| field.0,
+            |field| format!("duplicate field `{}`", field.0)
+        );
+        
+        assert_eq!(Ok(vec![("x", 0), ("y", 0)]), fields(vec!["x", "y"]).run(TestScanner::new("")));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("duplicate field `x`"), ParseError::new("duplicate field `x`")])),
+                   fields(vec!["x", "y", "x", "x"]).run(TestScanner::new("")));
     }
     
     #[derive(Debug, PartialEq)]
@@ -204,6 +1053,7 @@ mod tests {
     impl scanner::Scanner for TestScanner {
         
         type Token = TestToken; // Unimportant
+        type Position = usize;
         
         fn from_scanner(other: &Self) -> Self {
             TestScanner {
@@ -227,6 +1077,10 @@ mod tests {
         fn next_token(&self) -> Rc<Self::Token> {
             Rc::new(TestToken {})
         }
+        
+        fn position(&self) -> usize {
+            0
+        }
     }
     
     struct TestToken {}
@@ -1,54 +1,458 @@
+use crate::error::{has_errors, ParseError};
 use crate::parser::Parser;
-use crate::scanner::{Scanner, Token};
+use crate::scanner::{Lexeme, Scanner, Token, TokenTypeIndex, TokenValue, Trivia};
+use crate::scanner::history::TokenHistory;
+use crate::scanner::preprocessed::PreprocessedScanner;
+use crate::scanner::scoped::Scopes;
+use crate::scanner::slice::SliceScanner;
+use crate::scanner::token_set::TokenSet;
 
 use std::rc::Rc;
 
+// The most used of these are written directly over the scanner rather than
+// with `>>`, since every parse runs them many times
+
 pub fn is_at_end<S>() -> Parser<S, bool>
 where S: 'static + Scanner {
-    Parser::get_scanner() >> |scanner: S|
-    Parser::result(scanner.is_finished())
+    Parser::primitive(|scanner: S| (scanner.is_finished(), scanner, vec![]))
+}
+
+// Succeeds only if all of the input has been consumed
+pub fn eof<S>() -> Parser<S, ()>
+where S: 'static + Scanner {
+    is_at_end().if_else(
+        Parser::result(()),
+        Parser::get_scanner() >> |scanner: S|
+            Parser::fail((), ParseError::new("expected end of input, found extra tokens").at(scanner.position()))
+    )
 }
 
 pub fn previous<S>() -> Parser<S, Rc<S::Token>>
 where S: 'static + Scanner {
-    Parser::get_scanner() >> |scanner: S|
-    Parser::result(scanner.current_token())
+    Parser::primitive(|scanner: S| (scanner.current_token(), scanner, vec![]))
 }
 
 pub fn peek<S>() -> Parser<S, Rc<S::Token>>
 where S: 'static + Scanner {
-    Parser::get_scanner() >> |scanner: S|
-    Parser::result(scanner.next_token())
+    Parser::primitive(|scanner: S| (scanner.next_token(), scanner, vec![]))
+}
+
+// The token `n` places ahead without consuming anything; see `Scanner::peek_n`
+pub fn peek_n<S>(n: usize) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner {
+    Parser::get_scanner() >> move |scanner: S|
+    Parser::result(scanner.peek_n(n))
 }
 
 pub fn advance<S>() -> Parser<S, Rc<S::Token>>
 where S: 'static + Scanner {
-    Parser::get_scanner() >> |scanner: S|
-    Parser::set_scanner(scanner.scan_token()) >> |_|
-    previous()
+    Parser::primitive(|scanner: S| {
+        let scanner = scanner.scan_token();
+        (scanner.current_token(), scanner, vec![])
+    })
+}
+
+pub fn current_position<S>() -> Parser<S, S::Position>
+where S: 'static + Scanner {
+    Parser::primitive(|scanner: S| (scanner.position(), scanner, vec![]))
+}
+
+// Runs `p`, also returning the positions before and after it
+pub fn span_of<S, T>(p: Parser<S, T>) -> Parser<S, (T, S::Position, S::Position)>
+where S: 'static + Scanner {
+    current_position() >> |start|
+    p >> move |value|
+    current_position() >> move |end|
+    Parser::result((value, start, end))
+}
+
+// A token along with the trivia around it, each as the positions before and
+// after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithTrivia<T, P> {
+    pub token: Rc<T>,
+    pub leading: (P, P),
+    pub trailing: (P, P)
+}
+
+// Like `consume`, but also skipping the trivia around the token: all of it
+// before the token, and after it up to and including the end of its line, so
+// the next token's leading trivia starts on the line after
+pub fn token_with_trivia<S>(t_type: <S::Token as Token>::TokenType, message: String)
+-> Parser<S, WithTrivia<S::Token, S::Position>>
+where S: 'static + Scanner,
+      S::Token: Trivia {
+    span_of(skip_trivia(false)) >> move |(_, leading_start, leading_end)|
+    consume(t_type, message) >> move |token|
+    span_of(skip_trivia(true)) >> move |(_, trailing_start, trailing_end)|
+    Parser::result(WithTrivia {
+        token,
+        leading: (leading_start, leading_end),
+        trailing: (trailing_start, trailing_end)
+    })
+}
+
+fn skip_trivia<S>(to_line_end: bool) -> Parser<S, ()>
+where S: 'static + Scanner,
+      S::Token: Trivia {
+    Parser::primitive(move |mut scanner: S| {
+        while !scanner.is_finished() && scanner.next_token().is_trivia() {
+            scanner = scanner.scan_token();
+            if to_line_end && scanner.current_token().is_line_break() {
+                break;
+            }
+        }
+        ((), scanner, vec![])
+    })
+}
+
+fn next_is<S: Scanner>(scanner: &S, t_type: &<S::Token as Token>::TokenType) -> bool {
+    !scanner.is_finished() && scanner.next_token().t_type() == *t_type
 }
 
 pub fn check<S>(t_type: <S::Token as Token>::TokenType) -> Parser<S, bool>
-where S: 'static + Scanner{
+where S: 'static + Scanner {
+    Parser::primitive(move |scanner: S| (next_is(&scanner, &t_type), scanner, vec![]))
+}
+
+pub fn matches<S>(t_type: <S::Token as Token>::TokenType) -> Parser<S, bool>
+where S: 'static + Scanner {
+    Parser::primitive(move |scanner: S| if next_is(&scanner, &t_type) {
+        (true, scanner.scan_token(), vec![])
+    } else {
+        (false, scanner, vec![])
+    })
+}
+
+// Advances past the next token if it has type `t_type`, otherwise reports
+// `message` at the current position and returns the unexpected token without
+// consuming it
+pub fn consume<S>(t_type: <S::Token as Token>::TokenType, message: String) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner {
+    Parser::primitive(move |scanner: S| if next_is(&scanner, &t_type) {
+        let scanner = scanner.scan_token();
+        (scanner.current_token(), scanner, vec![])
+    } else {
+        let error = ParseError::new(message).at(scanner.position());
+        (scanner.next_token(), scanner, vec![error])
+    })
+}
+
+// Advances past the next token if `pred` holds for it, otherwise reports an
+// error at the current position without consuming anything
+pub fn satisfy<S, P>(pred: P) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner,
+      P: 'static + Fn(&S::Token) -> bool {
+    satisfy_expecting(pred, None)
+}
+
+fn satisfy_expecting<S, P>(pred: P, expected: Option<String>) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner,
+      P: 'static + Fn(&S::Token) -> bool {
+    Parser::get_scanner() >> move |scanner: S|
+    if !scanner.is_finished() && pred(&*scanner.next_token()) {
+        advance()
+    } else {
+        let error = ParseError { expected, ..ParseError::new("unexpected token").at(scanner.position()) };
+        Parser::fail(scanner.next_token(), error)
+    }
+}
+
+// Advances, returning the payload of the consumed token
+pub fn token_value<S>() -> Parser<S, <S::Token as TokenValue>::Value>
+where S: 'static + Scanner,
+      S::Token: TokenValue {
+    advance().map(|token: Rc<S::Token>| token.value())
+}
+
+// Advances past the next token if its text is exactly `kw`, for languages
+// whose keywords are scanned as identifiers
+pub fn keyword<S>(kw: &str) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner,
+      S::Token: Lexeme {
+    let kw = kw.to_string();
+    let expected = format!("`{}`", kw);
+    satisfy_expecting(move |token: &S::Token| token.text() == kw, Some(expected))
+}
+
+// Like `keyword`, but ignoring ASCII case, so `SELECT` and `select` match
+pub fn keyword_ci<S>(kw: &str) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner,
+      S::Token: Lexeme {
+    let kw = kw.to_string();
+    let expected = format!("`{}`", kw);
+    satisfy_expecting(move |token: &S::Token| token.text().eq_ignore_ascii_case(&kw), Some(expected))
+}
+
+// Collects tokens for as long as `pred` holds, leaving the first token it
+// rejects. Never fails; if the next token is rejected the result is empty.
+pub fn take_while<S, P>(pred: P) -> Parser<S, Vec<Rc<S::Token>>>
+where S: 'static + Scanner,
+      P: 'static + Fn(&S::Token) -> bool {
+    Parser::primitive(move |mut scanner: S| {
+        let mut tokens = vec![];
+        while !scanner.is_finished() && pred(&*scanner.next_token()) {
+            scanner = scanner.scan_token();
+            tokens.push(scanner.current_token());
+        }
+        (tokens, scanner, vec![])
+    })
+}
+
+// Collects tokens up to, but not including, the next token of type `t_type`,
+// or up to the end of the input if there is none
+pub fn take_until<S>(t_type: <S::Token as Token>::TokenType) -> Parser<S, Vec<Rc<S::Token>>>
+where S: 'static + Scanner {
+    take_while(move |token: &S::Token| token.t_type() != t_type)
+}
+
+// Advances past the next token if its type is one of `types`. Token types
+// that implement `TokenTypeIndex` can use `one_of_set` instead, which doesn't
+// search through `types` for every token.
+pub fn one_of<S>(types: Vec<<S::Token as Token>::TokenType>) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner {
+    satisfy(move |token: &S::Token| types.contains(&token.t_type()))
+}
+
+// Advances past the next token if its type is not one of `types`. See
+// `none_of_set` for token types that implement `TokenTypeIndex`.
+pub fn none_of<S>(types: Vec<<S::Token as Token>::TokenType>) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner {
+    satisfy(move |token: &S::Token| !types.contains(&token.t_type()))
+}
+
+// Like `one_of`, looking the type up in `set`
+pub fn one_of_set<S>(set: TokenSet<<S::Token as Token>::TokenType>) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner,
+      <S::Token as Token>::TokenType: TokenTypeIndex {
+    satisfy(move |token: &S::Token| set.contains(&token.t_type()))
+}
+
+// Like `none_of`, looking the type up in `set`
+pub fn none_of_set<S>(set: TokenSet<<S::Token as Token>::TokenType>) -> Parser<S, Rc<S::Token>>
+where S: 'static + Scanner,
+      <S::Token as Token>::TokenType: TokenTypeIndex {
+    satisfy(move |token: &S::Token| !set.contains(&token.t_type()))
+}
+
+pub fn check_set<S>(set: TokenSet<<S::Token as Token>::TokenType>) -> Parser<S, bool>
+where S: 'static + Scanner,
+      <S::Token as Token>::TokenType: TokenTypeIndex {
     is_at_end().if_else(
         Parser::result(false),
         peek() >> move |token: Rc<S::Token>|
-            Parser::result(<S as Scanner>::Token::t_type(&*token) == t_type)
+            Parser::result(set.contains(&token.t_type()))
     )
 }
 
-pub fn matches<S>(t_type: <S::Token as Token>::TokenType) -> Parser<S, bool>
-where S: 'static + Scanner {
-    check(t_type).if_else(
+pub fn matches_set<S>(set: TokenSet<<S::Token as Token>::TokenType>) -> Parser<S, bool>
+where S: 'static + Scanner,
+      <S::Token as Token>::TokenType: TokenTypeIndex {
+    check_set(set).if_else(
         advance() >> |_|
             Parser::result(true),
         Parser::result(false)
     )
 }
 
+// Whether the upcoming tokens have exactly the types in `types`, in order.
+// Looks ahead on a copy of the scanner, so nothing is consumed.
+pub fn peek_sequence<S>(types: Vec<<S::Token as Token>::TokenType>) -> Parser<S, bool>
+where S: 'static + Scanner {
+    Parser::get_scanner() >> move |scanner: S| {
+        let mut scanner = scanner;
+        for t_type in types {
+            if scanner.is_finished() || scanner.next_token().t_type() != t_type {
+                return Parser::result(false);
+            }
+            scanner = scanner.scan_token();
+        }
+        Parser::result(true)
+    }
+}
+
+// Skips tokens until the next one has a type in `types` (for example the
+// keywords that start a statement) or the input runs out. The matching token
+// is left for the caller to parse.
+pub fn recover_at_keywords<S>(types: Vec<<S::Token as Token>::TokenType>) -> Parser<S, ()>
+where S: 'static + Scanner {
+    is_at_end() >> |at_end|
+    if at_end {
+        Parser::result(())
+    } else {
+        peek() >> move |token: Rc<S::Token>|
+        if types.contains(&token.t_type()) {
+            Parser::result(())
+        } else {
+            advance() >> |_|
+            recover_at_keywords(types)
+        }
+    }
+}
+
+pub fn recent_tokens<S>(k: usize) -> Parser<S, Vec<Rc<S::Token>>>
+where S: 'static + TokenHistory {
+    Parser::get_scanner() >> move |scanner: S|
+    Parser::result(scanner.recent_tokens(k))
+}
+
+// Rewrites the rest of the tokens with `transform`, as described for
+// `PreprocessedScanner`, then runs `parser` over the result. Errors point at
+// the original tokens, and afterwards the scanner carries on from the
+// original position that `parser` stopped at.
+pub fn preprocess<T, U, F>(transform: F, parser: Parser<PreprocessedScanner<T>, U>) -> Parser<SliceScanner<T>, U>
+where T: 'static + Token + Clone,
+      U: 'static,
+      F: 'static + FnOnce(Vec<(usize, T)>) -> Vec<(usize, T)> {
+    Parser::primitive(move |scanner: SliceScanner<T>| {
+        let (tokens, end) = scanner.rest();
+        let preprocessed = PreprocessedScanner::starting_at(scanner.position(), tokens, end, transform);
+        let (value, preprocessed, errors) = parser.evaluate(preprocessed);
+        (value, scanner.seek(preprocessed.original_position()), errors)
+    })
+}
+
+// Parses the statements of a block up to and including its `close` token,
+// with statements separated by `sep`. A broken statement has its errors kept,
+// then the block skips to the next `sep` or `close` and carries on, so every
+// good statement and every error in the block is collected.
+pub fn block_body<S, T, F>(stmt: F, sep: <S::Token as Token>::TokenType, close: <S::Token as Token>::TokenType)
+-> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      <S::Token as Token>::TokenType: Clone,
+      F: 'static + Fn() -> Parser<S, T> {
+    Parser::primitive(move |mut scanner: S| {
+        let mut stmts = vec![];
+        let mut errors = vec![];
+        loop {
+            let (closed, s, _) = matches(close.clone()).evaluate(scanner);
+            scanner = s;
+            if closed {
+                return (stmts, scanner, errors);
+            } else if scanner.is_finished() {
+                errors.push(ParseError::new("expected end of block").at(scanner.position()));
+                return (stmts, scanner, errors);
+            }
+            
+            let (value, s, mut stmt_errors) = stmt().evaluate(scanner);
+            scanner = s;
+            let failed = has_errors(&stmt_errors);
+            errors.append(&mut stmt_errors);
+            if !failed {
+                stmts.push(value);
+                let (separated, s, _) = check(sep.clone()).or(check(close.clone())).evaluate(scanner);
+                scanner = s;
+                if !separated {
+                    errors.push(ParseError::new("expected separator after statement").at(scanner.position()));
+                }
+            }
+            
+            let after = matches(sep.clone());
+            let recover = recover_at_keywords(vec![sep.clone(), close.clone()]) >> |_| after;
+            let (_, s, _) = recover.evaluate(scanner);
+            scanner = s;
+        }
+    })
+}
+
+// A condition and the parser to run when it holds, both built on demand
+pub type Branch<S, T> = (Box<dyn FnOnce() -> Parser<S, bool>>, Box<dyn FnOnce() -> Parser<S, T>>);
+
+// A parser-level `if`/`else if` chain. The conditions are run in order, from
+// the first branch to the last, and the parser of the first one that holds is
+// run; if none holds, `otherwise` is. Nothing is built for the conditions
+// after the chosen branch, or for the parsers of branches that aren't taken.
+pub fn multi_if<S, T, O>(branches: Vec<Branch<S, T>>, otherwise: O) -> Parser<S, T>
+where S: 'static + Scanner,
+      O: 'static + FnOnce() -> Parser<S, T> {
+    fn chain<S, T, O>(mut branches: std::vec::IntoIter<Branch<S, T>>, otherwise: O) -> Parser<S, T>
+    where S: 'static + Scanner,
+          O: 'static + FnOnce() -> Parser<S, T> {
+        match branches.next() {
+            None => otherwise(),
+            Some((cond, branch)) => cond() >> move |holds|
+                if holds {
+                    branch()
+                } else {
+                    chain(branches, otherwise)
+                }
+        }
+    }
+    chain(branches.into_iter(), otherwise)
+}
+
+// Skips tokens until the next one has type `t_type` or the input runs out,
+// leaving the matching token unconsumed
+pub fn skip_until<S>(t_type: <S::Token as Token>::TokenType) -> Parser<S, ()>
+where S: 'static + Scanner {
+    recover_at_keywords(vec![t_type])
+}
+
+// Runs `body` inside a new innermost scope, discarding the scope afterwards
+pub fn enter_scope<S, T>(body: Parser<S, T>) -> Parser<S, T>
+where S: 'static + Scopes {
+    Parser::get_scanner() >> |scanner: S|
+    Parser::set_scanner(scanner.push_scope()) >> |_|
+    body >> |value|
+    Parser::get_scanner() >> |scanner: S|
+    Parser::set_scanner(scanner.pop_scope()) >> |_|
+    Parser::result(value)
+}
+
+pub fn declare<S>(name: String, info: S::Info) -> Parser<S, ()>
+where S: 'static + Scopes {
+    Parser::get_scanner() >> |scanner: S|
+    Parser::set_scanner(scanner.declare(name, info))
+}
+
+pub fn lookup<S>(name: String) -> Parser<S, Option<S::Info>>
+where S: 'static + Scopes {
+    Parser::get_scanner() >> move |scanner: S|
+    Parser::result(scanner.lookup(&name))
+}
+
+// Consumes the rest of the input, splitting it into groups at every token
+// matching `pred`. The separators themselves are dropped, as is an empty
+// group left by a separator at the very end of the input.
+pub fn split_at<S, F>(pred: F) -> Parser<S, Vec<Vec<Rc<S::Token>>>>
+where S: 'static + Scanner,
+      F: 'static + Fn(&S::Token) -> bool {
+    split_groups(Rc::new(pred), vec![], vec![])
+}
+
+fn split_groups<S, F>(pred: Rc<F>, groups: Vec<Vec<Rc<S::Token>>>, group: Vec<Rc<S::Token>>)
+-> Parser<S, Vec<Vec<Rc<S::Token>>>>
+where S: 'static + Scanner,
+      F: 'static + Fn(&S::Token) -> bool {
+    is_at_end() >> move |at_end| {
+        let mut groups = groups;
+        let mut group = group;
+        if at_end {
+            if !group.is_empty() {
+                groups.push(group);
+            }
+            Parser::result(groups)
+        } else {
+            advance() >> move |token: Rc<S::Token>| {
+                if pred(&token) {
+                    groups.push(group);
+                    group = vec![];
+                } else {
+                    group.push(token);
+                }
+                split_groups(pred, groups, group)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::do_parse;
+    use crate::error::ParseErrors;
+    use crate::scanner::history::HistoryScanner;
+    use crate::scanner::scoped::ScopedScanner;
     
     #[test]
     fn is_at_end_test() {
@@ -56,6 +460,65 @@ mod tests {
         assert_eq!(Ok(true), is_at_end().run(TestScanner::new(vec![])));
     }
     
+    #[test]
+    fn eof_test() {
+        assert_eq!(Ok(()), eof().run(TestScanner::new(vec![])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected end of input, found extra tokens").at(0)])),
+                   eof().run(TestScanner::new(vec![TestToken::a()])));
+    }
+    
+    #[test]
+    fn parse_to_end_test() {
+        let scanner = TestScanner::new(vec![TestToken::a()]);
+        assert_eq!(Ok(TokenType::A), advance().map(|token: Rc<TestToken>| token.t_type()).parse_to_end().run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected end of input, found extra tokens").at(1)])),
+                   advance().map(|_| ()).parse_to_end().run(scanner));
+    }
+    
+    #[test]
+    fn do_parse_test() {
+        let parser = do_parse! {
+            a <- advance();
+            b <- advance();
+            return (a, b);
+        };
+        let (a, b) = parser.run(TestScanner::new(vec![TestToken::a(), TestToken::b()])).unwrap();
+        assert_eq!((TokenType::A, TokenType::B), (a.t_type(), b.t_type()));
+    }
+    
+    #[test]
+    fn from_fn_test() {
+        let pair = || Parser::from_fn(|scanner: TestScanner| {
+            let scanner = scanner.scan_token();
+            let first = scanner.current_token();
+            if scanner.is_finished() {
+                return ((first.t_type(), TokenType::None), scanner, vec!["expected a second token"]);
+            }
+            let scanner = scanner.scan_token();
+            ((first.t_type(), scanner.current_token().t_type()), scanner, vec![])
+        });
+        
+        assert_eq!(Ok(((TokenType::A, TokenType::B), 2)),
+            (pair() >> |pair|
+            current_position() >> move |position|
+            Parser::result((pair, position))).run(TestScanner::new(vec![TestToken::a(), TestToken::b()])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected a second token")])),
+                   pair().run(TestScanner::new(vec![TestToken::a()])));
+    }
+    
+    #[test]
+    fn run_partial_test() {
+        let parser = advance() >> |token: Rc<TestToken>|
+                     Parser::error(token.t_type(), "unexpected end".to_string());
+        let (value, scanner, errors) = parser.run_partial(TestScanner::new(vec![TestToken::a(), TestToken::b()]));
+        assert_eq!(TokenType::A, value);
+        assert_eq!(1, scanner.position());
+        assert_eq!(TokenType::B, scanner.next_token().t_type());
+        assert_eq!(vec![ParseError::new("unexpected end")], errors);
+    }
+    
     #[test]
     fn previous_test() {
         let scanner = TestScanner::new(vec![TestToken::a()]);
@@ -70,6 +533,54 @@ mod tests {
         assert_eq!(Ok(next_token), peek().run(TestScanner::new(vec![TestToken::a()])));
     }
     
+    #[test]
+    fn peek_n_test() {
+        let scanner = || TestScanner::new(vec![TestToken::a(), TestToken::b(), TestToken(TokenType::Let)]);
+        assert_eq!(Ok(TokenType::B), peek_n(2).map(|token: Rc<TestToken>| token.t_type()).run(scanner()));
+        assert_eq!(Ok(TokenType::Let), peek_n(3).map(|token: Rc<TestToken>| token.t_type()).run(scanner()));
+        assert_eq!(Ok(TokenType::None), peek_n(5).map(|token: Rc<TestToken>| token.t_type()).run(scanner()));
+        assert_eq!(Ok(0), (peek_n(2) >> |_| current_position()).run(scanner()));
+        
+        // Looking far past the end stops scanning there
+        let near_end = || advance() >> |_| advance() >> |_| peek_n(10);
+        assert_eq!(Ok(TokenType::None), near_end().map(|token: Rc<TestToken>| token.t_type()).run(scanner()));
+    }
+    
+    #[test]
+    fn position_test() {
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        assert_eq!(0, scanner.position());
+        let scanner = scanner.scan_token();
+        assert_eq!(1, scanner.position());
+        let scanner = scanner.scan_token();
+        assert_eq!(2, scanner.position());
+        
+        // Scanning past the end doesn't move the position
+        let scanner = scanner.scan_token();
+        assert_eq!(2, scanner.position());
+    }
+    
+    #[test]
+    fn current_position_test() {
+        assert_eq!(Ok((0, 1)),
+            (current_position() >> |before|
+            advance() >> move |_|
+            current_position() >> move |after|
+            Parser::result((before, after))).run(TestScanner::new(vec![TestToken::a()])));
+    }
+    
+    #[test]
+    fn span_of_test() {
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b(), TestToken::a()]);
+        let (value, start, end) = (advance() >> |_| span_of(advance())).run(scanner).unwrap();
+        assert_eq!(TokenType::B, value.t_type());
+        assert_eq!((1, 2), (start, end));
+        
+        let scanner = TestScanner::new(vec![TestToken::a()]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("kept")])),
+                   span_of(Parser::error((), "kept".to_string())).run(scanner));
+    }
+    
     #[test]
     fn check_test() {
         assert_eq!(Ok(true), check(TokenType::A).run(TestScanner::new(vec![TestToken::a()])));
@@ -94,6 +605,34 @@ mod tests {
         ).run(TestScanner::new(vec![TestToken::a()])).is_ok());
     }
     
+    #[test]
+    fn attempt_test() {
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        let parser = (advance() >> |_| Parser::error((), "boom".to_string())).attempt();
+        let ((), scanner, errors) = parser.evaluate(scanner);
+        assert_eq!(0, scanner.position());
+        assert_eq!(vec![ParseError::new("boom")], errors);
+        
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        let (_, scanner, errors) = advance().attempt().evaluate(scanner);
+        assert_eq!(1, scanner.position());
+        assert!(errors.is_empty());
+    }
+    
+    #[test]
+    fn lookahead_test() {
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        assert_eq!(Ok((TokenType::A, TokenType::A, 0)),
+            (advance().lookahead() >> |ahead: Rc<TestToken>|
+            peek() >> move |next: Rc<TestToken>|
+            current_position() >> move |position|
+            Parser::result((ahead.t_type(), next.t_type(), position))).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken::a()]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("kept")])),
+            (advance() >> |_| Parser::error((), "kept".to_string())).lookahead().run(scanner));
+    }
+    
     #[test]
     fn matches_test() {
         assert_eq!(Ok(true), matches(TokenType::A).run(TestScanner::new(vec![TestToken::a()])));
@@ -118,23 +657,432 @@ mod tests {
             previous()).run(TestScanner::new(vec![TestToken::a()])));
     }
     
-    struct TestScanner {
+    #[test]
+    fn consume_test() {
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        let next_token = TestScanner::new(vec![TestToken::a()]).scan_token().current_token();
+        assert_eq!(Ok(next_token), consume(TokenType::A, "expected A".to_string()).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken::b()]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected A").at(0)])),
+                   consume(TokenType::A, "expected A".to_string()).run(scanner));
+        
+        // The unexpected token is not consumed
+        let scanner = TestScanner::new(vec![TestToken::b()]);
+        let (token, scanner, _) = consume(TokenType::A, "expected A".to_string()).evaluate(scanner);
+        assert_eq!(TokenType::B, token.t_type());
+        assert_eq!(0, scanner.position());
+    }
+    
+    #[test]
+    fn satisfy_test() {
+        let is_a = || satisfy(|token: &TestToken| token.t_type() == TokenType::A);
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        assert_eq!(Ok((TokenType::A, 1)),
+            (is_a() >> |token: Rc<TestToken>|
+            current_position() >> move |position|
+            Parser::result((token.t_type(), position))).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken::b()]);
+        let (token, scanner, errors) = is_a().evaluate(scanner);
+        assert_eq!(TokenType::B, token.t_type());
+        assert_eq!(0, scanner.position());
+        assert_eq!(vec![ParseError::new("unexpected token").at(0)], errors);
+        
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("unexpected token").at(0)])), is_a().run(TestScanner::new(vec![])));
+    }
+    
+    #[test]
+    fn take_while_test() {
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::a(), TestToken::b()]);
+        assert_eq!(Ok((vec![TokenType::A, TokenType::A], TokenType::B)),
+            (take_while(|token: &TestToken| token.t_type() == TokenType::A) >> |tokens: Vec<Rc<TestToken>>|
+            peek() >> move |next: Rc<TestToken>|
+            Parser::result((tokens.iter().map(|token| token.t_type()).collect(), next.t_type()))).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken::b()]);
+        assert_eq!(Ok(0), take_while(|token: &TestToken| token.t_type() == TokenType::A).map(|tokens| tokens.len()).run(scanner));
+        let scanner = TestScanner::new(vec![TestToken::a()]);
+        assert_eq!(Ok(1), take_while(|_: &TestToken| true).map(|tokens| tokens.len()).run(scanner));
+    }
+    
+    #[test]
+    fn take_until_test() {
+        let types = |tokens: Vec<Rc<TestToken>>| tokens.iter().map(|token| token.t_type()).collect::<Vec<_>>();
+        
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::a(), TestToken(TokenType::Semicolon)]);
+        assert_eq!(Ok((vec![TokenType::A, TokenType::A], TokenType::Semicolon)),
+            (take_until(TokenType::Semicolon).map(types) >> |tokens|
+            peek() >> move |next: Rc<TestToken>|
+            Parser::result((tokens, next.t_type()))).run(scanner));
+        
+        // Stops at the end of the input when there is no terminator
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        assert_eq!(Ok(vec![TokenType::A, TokenType::B]), take_until(TokenType::Semicolon).map(types).run(scanner));
+    }
+    
+    #[test]
+    fn token_value_test() {
+        use crate::scanner::slice::SliceScanner;
+        
+        #[derive(Debug, Clone)]
+        struct Number(i64);
+        impl Token for Number {
+            type TokenType = ();
+            fn t_type(&self) {}
+        }
+        impl TokenValue for Number {
+            type Value = i64;
+            fn value(&self) -> i64 {
+                self.0
+            }
+        }
+        let scanner = SliceScanner::new(vec![Number(4), Number(2)], Number(0));
+        
+        assert_eq!(Ok(42),
+            (token_value() >> |tens|
+            token_value() >> move |ones|
+            Parser::result(tens * 10 + ones)).run(scanner));
+    }
+    
+    #[test]
+    fn token_with_trivia_test() {
+        use crate::error::Position;
+        use crate::scanner::string::StringScanner;
+        
+        let token = |ch| token_with_trivia(ch, format!("expected {}", ch));
+        let (x, y) = (token('x') >> move |x| token('y').map(move |y| (x, y)))
+            .run(StringScanner::new("  x \n  y")).unwrap();
+        
+        assert_eq!(Position::new(2, 1, 3), x.token.position);
+        assert_eq!((Position::new(0, 1, 1), Position::new(2, 1, 3)), x.leading);
+        assert_eq!((Position::new(3, 1, 4), Position::new(5, 2, 1)), x.trailing);
+        assert_eq!((Position::new(5, 2, 1), Position::new(7, 2, 3)), y.leading);
+        assert_eq!((Position::new(8, 2, 4), Position::new(8, 2, 4)), y.trailing);
+        
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected x").at(Position::new(1, 1, 2))])),
+                   token('x').run(StringScanner::new(" y")));
+    }
+    
+    #[test]
+    fn keyword_test() {
+        use crate::scanner::slice::SliceScanner;
+        
+        #[derive(Debug, Clone)]
+        struct Word(&'static str);
+        impl Token for Word {
+            type TokenType = ();
+            fn t_type(&self) {}
+        }
+        impl Lexeme for Word {
+            fn text(&self) -> &str {
+                self.0
+            }
+        }
+        let words = |words: Vec<&'static str>| SliceScanner::new(words.into_iter().map(Word).collect(), Word(""));
+        
+        assert_eq!(Ok("if"), keyword("if").map(|word: Rc<Word>| word.0).run(words(vec!["if", "x"])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("unexpected token").expected("`if`").at(0)])),
+                   keyword("if").map(|_| ()).run(words(vec!["ifx"])));
+        assert!(keyword("if").run(words(vec!["IF"])).is_err());
+        
+        assert_eq!(Ok("SeLeCt"), keyword_ci("select").map(|word: Rc<Word>| word.0).run(words(vec!["SeLeCt"])));
+        assert!(keyword_ci("select").run(words(vec!["selects"])).is_err());
+    }
+    
+    #[test]
+    fn one_of_test() {
+        let scanner = TestScanner::new(vec![TestToken::b()]);
+        assert_eq!(Ok(TokenType::B),
+            one_of(vec![TokenType::A, TokenType::B]).map(|token: Rc<TestToken>| token.t_type()).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken(TokenType::Let)]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("unexpected token").at(0)])),
+            one_of(vec![TokenType::A, TokenType::B]).map(|_| ()).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken(TokenType::Let)]);
+        assert_eq!(Ok(TokenType::Let),
+            none_of(vec![TokenType::A, TokenType::B]).map(|token: Rc<TestToken>| token.t_type()).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken::a()]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("unexpected token").at(0)])),
+            none_of(vec![TokenType::A, TokenType::B]).map(|_| ()).run(scanner));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("unexpected token").at(0)])),
+            none_of(vec![TokenType::A]).map(|_| ()).run(TestScanner::new(vec![])));
+    }
+    
+    #[test]
+    fn check_set_test() {
+        let all = [TokenType::A, TokenType::B, TokenType::Let, TokenType::Fn];
+        let subsets = [vec![], vec![TokenType::A], vec![TokenType::B, TokenType::Fn], all.to_vec()];
+        
+        // Indexed lookup agrees with a linear search over the same types
+        for subset in subsets.iter() {
+            for t_type in all.iter() {
+                let scanner = TestScanner::new(vec![TestToken(t_type.clone())]);
+                let set = subset.iter().cloned().collect();
+                assert_eq!(Ok(subset.contains(t_type)), check_set(set).run(scanner));
+                
+                let scanner = || TestScanner::new(vec![TestToken(t_type.clone())]);
+                let set = || subset.iter().cloned().collect();
+                assert_eq!(one_of(subset.clone()).map(|_| ()).run(scanner()),
+                           one_of_set(set()).map(|_| ()).run(scanner()));
+                assert_eq!(none_of(subset.clone()).map(|_| ()).run(scanner()),
+                           none_of_set(set()).map(|_| ()).run(scanner()));
+            }
+        }
+        
+        let set: TokenSet<_> = vec![TokenType::A].into_iter().collect();
+        assert_eq!(Ok(false), check_set(set.clone()).run(TestScanner::new(vec![])));
+        assert_eq!(Ok((true, TokenType::B)),
+            (matches_set(set) >> |matched|
+            peek() >> move |token: Rc<TestToken>|
+            Parser::result((matched, token.t_type()))).run(TestScanner::new(vec![TestToken::a(), TestToken::b()])));
+    }
+    
+    #[test]
+    fn peek_sequence_test() {
+        let scanner = || TestScanner::new(vec![TestToken::a(), TestToken::b(), TestToken::a()]);
+        assert_eq!(Ok(true), peek_sequence(vec![TokenType::A, TokenType::B]).run(scanner()));
+        assert_eq!(Ok(true), peek_sequence(vec![]).run(scanner()));
+        assert_eq!(Ok(false), peek_sequence(vec![TokenType::A, TokenType::A]).run(scanner()));
+        assert_eq!(Ok(false),
+            peek_sequence(vec![TokenType::A, TokenType::B, TokenType::A, TokenType::A]).run(scanner()));
+        
+        // Nothing is consumed
+        assert_eq!(Ok(TestToken::a()),
+            (peek_sequence(vec![TokenType::A, TokenType::B]) >> |_|
+            peek() >> |token: Rc<TestToken>|
+            Parser::result((*token).clone())).run(scanner()));
+    }
+    
+    #[test]
+    fn recover_at_keywords_test() {
+        let scanner = TestScanner::new(vec![
+            TestToken::a(), TestToken::b(), TestToken(TokenType::Let), TestToken::a()
+        ]);
+        assert_eq!(Ok(TestToken(TokenType::Let)),
+            (recover_at_keywords(vec![TokenType::Let, TokenType::Fn]) >> |_|
+            peek() >> |token: Rc<TestToken>|
+            Parser::result((*token).clone())).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        assert_eq!(Ok(true),
+            (recover_at_keywords(vec![TokenType::Let]) >> |_|
+            is_at_end()).run(scanner));
+    }
+    
+    #[test]
+    fn recent_tokens_test() {
+        let scanner = HistoryScanner::new(TestScanner::new(vec![
+            TestToken::a(), TestToken::b(), TestToken(TokenType::Let), TestToken::a()
+        ]), 2);
+        fn history(tokens: Vec<Rc<TestToken>>) -> Vec<TokenType> {
+            tokens.iter().map(|token| token.t_type()).collect()
+        }
+        
+        assert_eq!(Ok((vec![], vec![TokenType::A], vec![TokenType::B, TokenType::Let])),
+            (recent_tokens(2) >> |none|
+            advance() >> |_|
+            recent_tokens(2) >> move |one|
+            advance() >> |_|
+            advance() >> |_|
+            recent_tokens(3) >> move |two|
+            Parser::result((history(none), history(one), history(two)))).run(scanner));
+    }
+    
+    #[test]
+    fn preprocess_test() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Word {
+            Name,
+            Comment,
+            Other,
+            End
+        }
+        impl Token for Word {
+            type TokenType = Word;
+            fn t_type(&self) -> Word {
+                *self
+            }
+        }
+        let strip_comments = |tokens: Vec<(usize, Word)>| tokens.into_iter().filter(|(_, word)| *word != Word::Comment).collect();
+        let name = || consume(Word::Name, "expected name".to_string());
+        let scanner = |words| SliceScanner::new(words, Word::End);
+        
+        // The comments are gone, so the error is at the original position of `Other`
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected name").at(3)])),
+            (advance() >> move |_|
+            preprocess(strip_comments, name())).run(scanner(vec![Word::Name, Word::Comment, Word::Comment, Word::Other])));
+        
+        // Parsing carries on from where the preprocessed tokens were left
+        assert_eq!(Ok(3),
+            (preprocess(strip_comments, name()) >> |_|
+            current_position()).run(scanner(vec![Word::Comment, Word::Name, Word::Comment, Word::Other])));
+    }
+    
+    #[test]
+    fn scopes_test() {
+        fn lookup_all() -> Parser<ScopedScanner<TestScanner, i32>, (Option<i32>, Option<i32>)> {
+            lookup("x".to_string()) >> |x|
+            lookup("y".to_string()) >> move |y|
+            Parser::result((x, y))
+        }
+        let scanner = ScopedScanner::new(TestScanner::new(vec![TestToken::a()]));
+        
+        assert_eq!(Ok(((Some(1), None), (Some(2), Some(3)), (Some(1), None))),
+            (declare("x".to_string(), 1) >> |_|
+            lookup_all() >> move |outer|
+            enter_scope(
+                declare("x".to_string(), 2) >> |_|
+                enter_scope(
+                    declare("y".to_string(), 3) >> |_|
+                    lookup_all()
+                )
+            ) >> move |inner|
+            lookup_all() >> move |after|
+            Parser::result((outer, inner, after))).run(scanner));
+    }
+    
+    #[test]
+    fn multi_if_test() {
+        let scanner = || TestScanner::new(vec![TestToken::b()]);
+        
+        assert_eq!(Ok("otherwise"), multi_if(vec![], || Parser::result("otherwise")).run(scanner()));
+        
+        let parser = multi_if(vec![
+            (Box::new(|| check(TokenType::A)), Box::new(|| Parser::result("a"))),
+            (Box::new(|| check(TokenType::B)), Box::new(|| Parser::result("first b"))),
+            (Box::new(|| check(TokenType::B)), Box::new(|| Parser::result("second b"))),
+            (Box::new(|| panic!("not reached")), Box::new(|| panic!("not taken")))
+        ], || panic!("not taken"));
+        assert_eq!(Ok("first b"), parser.run(scanner()));
+        
+        let parser = multi_if(vec![
+            (Box::new(|| check(TokenType::A)), Box::new(|| panic!("not taken")))
+        ], || Parser::result("otherwise"));
+        assert_eq!(Ok("otherwise"), parser.run(scanner()));
+    }
+    
+    #[test]
+    fn skip_until_test() {
+        let scanner = TestScanner::new(vec![
+            TestToken::a(), TestToken::a(), TestToken(TokenType::Semicolon), TestToken::b()
+        ]);
+        assert_eq!(Ok((TokenType::Semicolon, 2)),
+            (skip_until(TokenType::Semicolon) >> |_|
+            peek() >> |token: Rc<TestToken>|
+            current_position() >> move |position|
+            Parser::result((token.t_type(), position))).run(scanner));
+        
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b()]);
+        assert_eq!(Ok(true), (skip_until(TokenType::Semicolon) >> |_| is_at_end()).run(scanner));
+    }
+    
+    #[test]
+    fn recover_with_test() {
+        fn stmt() -> Parser<TestScanner, TokenType> {
+            (consume(TokenType::A, "expected A".to_string()) >> |token: Rc<TestToken>|
+            consume(TokenType::Semicolon, "expected ;".to_string()) >> move |_|
+            Parser::result(token.t_type()))
+            .recover_with(||
+                skip_until(TokenType::Semicolon) >> |_|
+                matches(TokenType::Semicolon) >> |_|
+                Parser::result(TokenType::None))
+        }
+        
+        let scanner = TestScanner::new(vec![
+            TestToken::a(), TestToken::b(), TestToken(TokenType::Semicolon),
+            TestToken::a(), TestToken(TokenType::Semicolon),
+            TestToken::a(), TestToken::b(), TestToken(TokenType::Semicolon)
+        ]);
+        let (stmts, scanner, errors) = (stmt() >> |a| stmt() >> move |b| stmt() >> move |c| Parser::result(vec![a, b, c]))
+            .evaluate(scanner);
+        assert_eq!(vec![TokenType::None, TokenType::A, TokenType::None], stmts);
+        assert_eq!(vec![ParseError::new("expected ;").at(1), ParseError::new("expected ;").at(6)], errors);
+        assert!(scanner.is_finished());
+    }
+    
+    #[test]
+    fn block_body_test() {
+        fn stmt() -> Parser<TestScanner, TokenType> {
+            matches(TokenType::A).if_else(
+                Parser::result(TokenType::A),
+                Parser::error(TokenType::None, "expected statement".to_string())
+            )
+        }
+        let block = |tokens: Vec<TokenType>| {
+            let scanner = TestScanner::new(tokens.into_iter().map(TestToken).collect());
+            (block_body(stmt, TokenType::Semicolon, TokenType::RightBrace) >> |stmts|
+            is_at_end() >> move |at_end|
+            Parser::result((stmts, at_end))).run(scanner)
+        };
+        
+        assert_eq!(Ok((vec![TokenType::A, TokenType::A], true)),
+                   block(vec![TokenType::A, TokenType::Semicolon, TokenType::A, TokenType::RightBrace]));
+        assert_eq!(Ok((vec![], true)), block(vec![TokenType::RightBrace]));
+        
+        // The good statements are still parsed and the closing brace consumed
+        let scanner = TestScanner::new(vec![
+            TestToken::a(), TestToken(TokenType::Semicolon), TestToken::b(), TestToken(TokenType::Semicolon),
+            TestToken::a(), TestToken(TokenType::RightBrace)
+        ]);
+        let (stmts, scanner, errors) = block_body(stmt, TokenType::Semicolon, TokenType::RightBrace)
+            .evaluate(scanner);
+        assert_eq!(vec![TokenType::A, TokenType::A], stmts);
+        assert_eq!(vec![ParseError::new("expected statement")], errors);
+        assert!(scanner.is_finished());
+        
+        assert_eq!(Err(ParseErrors(vec![
+            ParseError::new("expected separator after statement").at(1),
+            ParseError::new("expected end of block").at(2)
+        ])), block(vec![TokenType::A, TokenType::A]));
+    }
+    
+    #[test]
+    fn split_at_test() {
+        let scanner = TestScanner::new(vec![
+            TestToken::a(), TestToken::a(), TestToken::b(), TestToken::a(), TestToken::b(), TestToken::b(),
+            TestToken::a(), TestToken::b()
+        ]);
+        let groups = split_at(|token: &TestToken| token.t_type() == TokenType::B).run(scanner)
+            .unwrap()
+            .into_iter()
+            .map(|group| group.iter().map(|token| token.t_type()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![
+            vec![TokenType::A, TokenType::A],
+            vec![TokenType::A],
+            vec![],
+            vec![TokenType::A]
+        ], groups);
+        
+        assert_eq!(Ok(vec![]), split_at(|_: &TestToken| true).run(TestScanner::new(vec![])));
+    }
+    
+    pub(crate) struct TestScanner {
         tokens: Vec<TestToken>,
-        is_at_start: usize
+        is_at_start: usize,
+        position: usize
     }
     impl TestScanner {
-        fn new(tokens: Vec<TestToken>) -> Self { TestScanner { tokens, is_at_start: 0 } }
+        pub(crate) fn new(tokens: Vec<TestToken>) -> Self { TestScanner { tokens, is_at_start: 0, position: 0 } }
     }
     impl Scanner for TestScanner {
         type Token = TestToken;
+        type Position = usize;
         
         fn from_scanner(scanner: &Self) -> Self {
             TestScanner {
                 tokens: scanner.tokens.clone(),
-                is_at_start: scanner.is_at_start
+                is_at_start: scanner.is_at_start,
+                position: scanner.position
             }
         }
         fn scan_token(mut self) -> Self {
+            if !self.is_finished() {
+                self.position += 1;
+            }
             if self.is_at_start == 0 {
                 self.is_at_start = 1;
             } else {
@@ -143,10 +1091,10 @@ mod tests {
             self
         }
         fn is_finished(&self) -> bool {
-            self.tokens.is_empty()
+            self.tokens.len() <= self.is_at_start
         }
         fn current_token(&self) -> Rc<Self::Token> {
-            match self.tokens.get(0) {
+            match self.tokens.first() {
                 _ if self.is_at_start == 0 => Rc::new(TestToken(TokenType::None)),
                 Some(a) => Rc::new(a.clone()),
                 None => Rc::new(TestToken(TokenType::None))
@@ -158,16 +1106,19 @@ mod tests {
                 None => Rc::new(TestToken(TokenType::None))
             }
         }
+        fn position(&self) -> usize {
+            self.position
+        }
     }
     
     #[derive(PartialEq, Clone, Debug)]
-    struct TestToken(TokenType);
+    pub(crate) struct TestToken(pub(crate) TokenType);
     impl TestToken {
-        fn a() -> Self {
+        pub(crate) fn a() -> Self {
             TestToken(TokenType::A)
         }
         
-        fn b() -> Self {
+        pub(crate) fn b() -> Self {
             TestToken(TokenType::B)
         }
     }
@@ -179,10 +1130,24 @@ mod tests {
     }
     
     #[derive(PartialEq, Clone, Debug)]
-    enum TokenType {
+    pub(crate) enum TokenType {
         A,
         B,
+        Let,
+        Fn,
+        Semicolon,
+        Comma,
+        RightBrace,
+        LeftParen,
+        RightParen,
         None
     }
     
+    impl TokenTypeIndex for TokenType {
+        const COUNT: usize = TokenType::None as usize + 1;
+        fn index(&self) -> usize {
+            self.clone() as usize
+        }
+    }
+    
 }
\ No newline at end of file
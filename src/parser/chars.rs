@@ -0,0 +1,197 @@
+use crate::error::ParseError;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::scanner::string::CharToken;
+
+// Parses a line comment (`// ...`) or a block comment (`/* ... */`), where
+// block comments can be nested, returning the text between the delimiters
+// along with the positions before and after the comment. A line comment ends
+// before the line break. Anything that isn't a comment is an error without
+// consuming anything, and so is a block comment left open at the end of the
+// input, reported where it starts.
+pub fn comment<S>() -> Parser<S, (String, S::Position, S::Position)>
+where S: 'static + Scanner<Token = CharToken> {
+    Parser::primitive(|scanner: S| {
+        let start = scanner.position();
+        let (first, second) = (scanner.peek_n(1).ch, scanner.peek_n(2).ch);
+        if scanner.is_finished() || first != '/' || (second != '/' && second != '*') {
+            let error = ParseError::new("expected comment").at(start.clone());
+            return ((String::new(), start.clone(), start), scanner, vec![error]);
+        }
+
+        let mut scanner = scanner.scan_token().scan_token();
+        let mut text = String::new();
+        if second == '/' {
+            while !scanner.is_finished() && scanner.next_token().ch != '\n' {
+                scanner = scanner.scan_token();
+                text.push(scanner.current_token().ch);
+            }
+            if text.ends_with('\r') {
+                text.pop();
+            }
+        } else {
+            let mut depth = 1;
+            while depth > 0 {
+                if scanner.is_finished() {
+                    let error = ParseError::new("unterminated block comment").at(start.clone());
+                    let end = scanner.position();
+                    return ((text, start, end), scanner, vec![error]);
+                }
+                match (scanner.peek_n(1).ch, scanner.peek_n(2).ch) {
+                    ('*', '/') => {
+                        depth -= 1;
+                        scanner = scanner.scan_token().scan_token();
+                        if depth > 0 {
+                            text.push_str("*/");
+                        }
+                    }
+                    ('/', '*') => {
+                        depth += 1;
+                        scanner = scanner.scan_token().scan_token();
+                        text.push_str("/*");
+                    }
+                    (ch, _) => {
+                        scanner = scanner.scan_token();
+                        text.push(ch);
+                    }
+                }
+            }
+        }
+        let end = scanner.position();
+        ((text, start, end), scanner, vec![])
+    })
+}
+
+// Parses a floating-point literal without a sign, such as `42`, `1.5`, `.5`,
+// `5.`, `2E+3` or `1.5e-10`. A dot followed by a letter, `_` or another dot
+// isn't part of the literal, so `1.foo` and `1..2` both start with the float
+// `1`. Anything that doesn't start with a digit, or a dot and a digit, is an
+// error without consuming anything. An exponent without digits, as in `1e` or
+// `1e+`, is an error where the digits were expected.
+pub fn float<S>() -> Parser<S, f64>
+where S: 'static + Scanner<Token = CharToken> {
+    Parser::primitive(|scanner: S| {
+        let (first, second) = (scanner.peek_n(1).ch, scanner.peek_n(2).ch);
+        let starts_float = first.is_ascii_digit() || (first == '.' && second.is_ascii_digit());
+        if !starts_float {
+            let error = ParseError::new("expected float").at(scanner.position());
+            return (0.0, scanner, vec![error]);
+        }
+
+        let (mut text, mut scanner) = digits(String::new(), scanner);
+        let after_dot = scanner.peek_n(2).ch;
+        if scanner.peek_n(1).ch == '.' && !(after_dot.is_alphabetic() || after_dot == '_' || after_dot == '.') {
+            text.push('.');
+            let (t, s) = digits(text, scanner.scan_token());
+            text = t;
+            scanner = s;
+        }
+
+        let exponent = scanner.peek_n(1).ch;
+        if exponent == 'e' || exponent == 'E' {
+            let signed = scanner.peek_n(2).ch == '+' || scanner.peek_n(2).ch == '-';
+            let first_digit = if signed { 3 } else { 2 };
+            if !scanner.peek_n(first_digit).ch.is_ascii_digit() {
+                let value = text.parse().unwrap_or(0.0);
+                for _ in 1..first_digit {
+                    scanner = scanner.scan_token();
+                }
+                let error = ParseError::new("expected exponent digits").at(scanner.position());
+                return (value, scanner, vec![error]);
+            }
+            for _ in 1..first_digit {
+                scanner = scanner.scan_token();
+                text.push(scanner.current_token().ch);
+            }
+            let (t, s) = digits(text, scanner);
+            text = t;
+            scanner = s;
+        }
+
+        // Only digits, a dot and a well-formed exponent were taken, which
+        // always parse
+        (text.parse().unwrap_or(0.0), scanner, vec![])
+    })
+}
+
+// Appends the digits that come next to `text`
+fn digits<S: Scanner<Token = CharToken>>(mut text: String, mut scanner: S) -> (String, S) {
+    while !scanner.is_finished() && scanner.next_token().ch.is_ascii_digit() {
+        scanner = scanner.scan_token();
+        text.push(scanner.current_token().ch);
+    }
+    (text, scanner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ParseErrors, Position};
+    use crate::parser::basic_functions::{peek, take_while};
+    use crate::scanner::string::StringScanner;
+
+    use std::rc::Rc;
+
+    fn run(source: &str) -> Result<(String, Position, Position), ParseErrors> {
+        comment().run(StringScanner::new(source))
+    }
+
+    #[test]
+    fn comment_test() {
+        assert_eq!(Ok((" note".to_string(), Position::new(0, 1, 1), Position::new(7, 1, 8))), run("// note"));
+        assert_eq!(Ok((" a * b ".to_string(), Position::new(0, 1, 1), Position::new(11, 1, 12))), run("/* a * b */"));
+
+        // The line break is left after a line comment
+        let parser = comment() >> |(text, _, _)| peek().map(move |next: Rc<CharToken>| (text, next.ch));
+        assert_eq!(Ok((" note".to_string(), '\n')), parser.run(StringScanner::new("// note\r\nx")));
+    }
+
+    #[test]
+    fn nested_comment_test() {
+        let source = "/* a /* b */ c */x";
+        assert_eq!(Ok((" a /* b */ c ".to_string(), Position::new(0, 1, 1), Position::new(17, 1, 18))), run(source));
+        assert_eq!(Ok(("\n/**/\n".to_string(), Position::new(0, 1, 1), Position::new(10, 3, 3))), run("/*\n/**/\n*/"));
+    }
+
+    #[test]
+    fn float_test() {
+        let parse = |source: &str| float().run(StringScanner::new(source));
+        assert_eq!(Ok(1.5e-10), parse("1.5e-10"));
+        assert_eq!(Ok(2000.0), parse("2E+3"));
+        assert_eq!(Ok(0.5), parse(".5"));
+        assert_eq!(Ok(5.0), parse("5."));
+        assert_eq!(Ok(42.0), parse("42"));
+        assert_eq!(Ok(1.25e3), parse("1.25e3"));
+
+        // A dot that starts a method call or a range is left alone
+        let rest = |source: &str| {
+            let parser = float() >> |value| take_while(|_: &CharToken| true).map(move |chars: Vec<Rc<CharToken>>| {
+                (value, chars.iter().map(|token| token.ch).collect::<String>())
+            });
+            parser.run(StringScanner::new(source))
+        };
+        assert_eq!(Ok((1.0, ".foo()".to_string())), rest("1.foo()"));
+        assert_eq!(Ok((1.0, "..2".to_string())), rest("1..2"));
+        assert_eq!(Ok((1.0, " + 2".to_string())), rest("1. + 2"));
+    }
+
+    #[test]
+    fn float_error_test() {
+        let parse = |source: &str| float().run(StringScanner::new(source));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected exponent digits").at(Position::new(2, 1, 3))])),
+                   parse("1e"));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected exponent digits").at(Position::new(5, 1, 6))])),
+                   parse("1.0e-x"));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected float").at(Position::new(0, 1, 1))])), parse("."));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected float").at(Position::new(0, 1, 1))])), parse("e5"));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected float").at(Position::new(0, 1, 1))])), parse(""));
+    }
+
+    #[test]
+    fn comment_error_test() {
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("unterminated block comment").at(Position::new(0, 1, 1))])),
+                   run("/* a /* b */"));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected comment").at(Position::new(0, 1, 1))])), run("/ a"));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected comment").at(Position::new(0, 1, 1))])), run(""));
+    }
+}
@@ -0,0 +1,1115 @@
+use crate::error::{has_errors, is_cut, ParseError, Position};
+use crate::parser::Parser;
+use crate::parser::basic_functions::{consume, current_position, matches};
+use crate::scanner::{Scanner, Token};
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// The outcome of running a parser once as part of a larger combinator
+enum Attempt<S, T> {
+    // Holds any warnings the attempt produced
+    Matched(T, S, Vec<ParseError>),
+    // Failed or succeeded without consuming anything; holds the scanner as
+    // it was before the attempt
+    NoMatch(S),
+    // Failed after consuming input
+    Failed(S, Vec<ParseError>)
+}
+
+fn try_parse<S, T>(p: Parser<S, T>, scanner: S) -> Attempt<S, T>
+where S: 'static + Scanner {
+    let start = scanner.position();
+    let checkpoint = scanner.checkpoint();
+    let (value, scanner, errors) = p.evaluate(scanner);
+    let progressed = scanner.position() != start;
+    match (!has_errors(&errors), progressed || is_cut(&errors)) {
+        (true, true) => Attempt::Matched(value, scanner, errors),
+        (false, true) => Attempt::Failed(scanner, errors),
+        (_, false) => Attempt::NoMatch(scanner.restore(checkpoint))
+    }
+}
+
+// Runs `p` as many times as it matches, collecting the values. An attempt that
+// fails without consuming anything is not a match: its errors are dropped,
+// the scanner is restored and the values so far are returned. An attempt that
+// fails after consuming input surfaces its errors and ends the repetition. An
+// attempt that succeeds without consuming also ends it (with its value
+// dropped), since repeating it would never terminate.
+pub fn many<S, T, F>(p: F) -> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, T> {
+    Parser::primitive(move |mut scanner: S| {
+        let mut items = vec![];
+        let mut warnings = vec![];
+        while !scanner.is_finished() {
+            match try_parse(p(), scanner) {
+                Attempt::Matched(value, s, mut w) => {
+                    items.push(value);
+                    warnings.append(&mut w);
+                    scanner = s;
+                }
+                Attempt::NoMatch(s) => return (items, s, warnings),
+                Attempt::Failed(s, mut errors) => {
+                    warnings.append(&mut errors);
+                    return (items, s, warnings);
+                }
+            }
+        }
+        (items, scanner, warnings)
+    })
+}
+
+// Like `many`, but only counts the matches instead of collecting them
+pub fn count<S, T: 'static, F>(p: F) -> Parser<S, usize>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, T> {
+    Parser::primitive(move |mut scanner: S| {
+        let mut matched = 0;
+        let mut warnings = vec![];
+        while !scanner.is_finished() {
+            match try_parse(p(), scanner) {
+                Attempt::Matched(_, s, mut w) => {
+                    matched += 1;
+                    warnings.append(&mut w);
+                    scanner = s;
+                }
+                Attempt::NoMatch(s) => return (matched, s, warnings),
+                Attempt::Failed(s, mut errors) => {
+                    warnings.append(&mut errors);
+                    return (matched, s, warnings);
+                }
+            }
+        }
+        (matched, scanner, warnings)
+    })
+}
+
+// Like `many`, but at least one match is required
+pub fn many1<S, T, F>(p: F) -> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, T> {
+    many(p) >> |items: Vec<T>|
+    if items.is_empty() {
+        Parser::error(items, "expected at least one".to_string())
+    } else {
+        Parser::result(items)
+    }
+}
+
+// Runs `p` exactly `n` times, stopping with its errors at the first failure
+pub fn repeat_n<S, T, F>(n: usize, p: F) -> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, T> {
+    Parser::primitive(move |mut scanner: S| {
+        let mut items = Vec::with_capacity(n);
+        let mut warnings = vec![];
+        for _ in 0..n {
+            let (value, s, mut errors) = p().evaluate(scanner);
+            let failed = has_errors(&errors);
+            warnings.append(&mut errors);
+            if failed {
+                return (items, s, warnings);
+            }
+            items.push(value);
+            scanner = s;
+        }
+        (items, scanner, warnings)
+    })
+}
+
+// Runs `item` until `terminator` matches, consuming the terminator and
+// returning the items. The terminator is tried before each item; reaching the
+// end of the input first is an error, as is an item that consumes nothing.
+pub fn many_till<S, T, E, F, G>(item: F, terminator: G) -> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      E: 'static,
+      F: 'static + Fn() -> Parser<S, T>,
+      G: 'static + Fn() -> Parser<S, E> {
+    Parser::primitive(move |mut scanner: S| {
+        let mut items = vec![];
+        let mut warnings = vec![];
+        loop {
+            if scanner.is_finished() {
+                warnings.push(ParseError::new("expected terminator before end of input").at(scanner.position()));
+                return (items, scanner, warnings);
+            }
+            match try_parse(terminator(), scanner) {
+                Attempt::Matched(_, s, mut w) | Attempt::Failed(s, mut w) => {
+                    warnings.append(&mut w);
+                    return (items, s, warnings);
+                }
+                Attempt::NoMatch(s) => scanner = s
+            }
+            
+            let start = scanner.position();
+            let (value, s, mut errors) = item().evaluate(scanner);
+            let failed = has_errors(&errors);
+            warnings.append(&mut errors);
+            if failed {
+                return (items, s, warnings);
+            }
+            items.push(value);
+            if s.position() == start {
+                warnings.push(ParseError::new("expected terminator").at(s.position()));
+                return (items, s, warnings);
+            }
+            scanner = s;
+        }
+    })
+}
+
+// Parses `init`, then folds each repetition of `step` into it with `combine`,
+// matching `step` the same way as `many`
+pub fn fold<S, T, A, F, C>(init: Parser<S, A>, step: F, combine: C) -> Parser<S, A>
+where S: 'static + Scanner,
+      T: 'static,
+      F: 'static + Fn() -> Parser<S, T>,
+      C: 'static + Fn(A, T) -> A {
+    init >> move |init: A|
+    Parser::primitive(move |mut scanner: S| {
+        let mut acc = init;
+        let mut warnings = vec![];
+        while !scanner.is_finished() {
+            match try_parse(step(), scanner) {
+                Attempt::Matched(value, s, mut w) => {
+                    acc = combine(acc, value);
+                    warnings.append(&mut w);
+                    scanner = s;
+                }
+                Attempt::NoMatch(s) => return (acc, s, warnings),
+                Attempt::Failed(s, mut errors) => {
+                    warnings.append(&mut errors);
+                    return (acc, s, warnings);
+                }
+            }
+        }
+        (acc, scanner, warnings)
+    })
+}
+
+// Parses `first` and `second` in lockstep, alternating between them for as
+// long as `first` matches, and pairs up their values, as with parameters and
+// the defaults given for them. `second` may stop matching before `first`
+// does, leaving the rest of the pairs without a second value. Both are
+// matched the same way as in `many`. If `second` still matches once `first`
+// has stopped, there are more of it than of `first`, which is an error.
+pub fn zip_longest<S, A, B, F, G>(first: F, second: G) -> Parser<S, Vec<(A, Option<B>)>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, A>,
+      G: 'static + Fn() -> Parser<S, B> {
+    Parser::primitive(move |mut scanner: S| {
+        let mut pairs = vec![];
+        let mut warnings = vec![];
+        let mut paired = true;
+        while !scanner.is_finished() {
+            let a = match try_parse(first(), scanner) {
+                Attempt::Matched(value, s, mut w) => {
+                    warnings.append(&mut w);
+                    scanner = s;
+                    value
+                }
+                Attempt::NoMatch(s) => {
+                    scanner = s;
+                    break;
+                }
+                Attempt::Failed(s, mut errors) => {
+                    warnings.append(&mut errors);
+                    return (pairs, s, warnings);
+                }
+            };
+            if !paired {
+                pairs.push((a, None));
+                continue;
+            }
+            match try_parse(second(), scanner) {
+                Attempt::Matched(b, s, mut w) => {
+                    pairs.push((a, Some(b)));
+                    warnings.append(&mut w);
+                    scanner = s;
+                }
+                Attempt::NoMatch(s) => {
+                    pairs.push((a, None));
+                    paired = false;
+                    scanner = s;
+                }
+                Attempt::Failed(s, mut errors) => {
+                    pairs.push((a, None));
+                    warnings.append(&mut errors);
+                    return (pairs, s, warnings);
+                }
+            }
+        }
+        if !paired {
+            return (pairs, scanner, warnings);
+        }
+        
+        let position = scanner.position();
+        let mut extra = 0;
+        while !scanner.is_finished() {
+            match try_parse(second(), scanner) {
+                Attempt::Matched(_, s, _) => {
+                    extra += 1;
+                    scanner = s;
+                }
+                Attempt::NoMatch(s) => {
+                    scanner = s;
+                    break;
+                }
+                Attempt::Failed(s, mut errors) => {
+                    warnings.append(&mut errors);
+                    return (pairs, s, warnings);
+                }
+            }
+        }
+        if extra > 0 {
+            let expected = pairs.len();
+            let message = format!("expected at most {} {}, found {}",
+                                  expected, if expected == 1 { "item" } else { "items" }, expected + extra);
+            warnings.push(ParseError::new(message).at(position));
+        }
+        (pairs, scanner, warnings)
+    })
+}
+
+// Parses `open`, `inner` and `close` in order, keeping only `inner`'s value.
+// Errors from all three are kept, so a missing `close` is reported while the
+// inner value is still returned.
+pub fn between<S, O, T, C, F, G>(open: F, inner: Parser<S, T>, close: G) -> Parser<S, T>
+where S: 'static + Scanner,
+      O: 'static,
+      C: 'static,
+      F: Fn() -> Parser<S, O>,
+      G: Fn() -> Parser<S, C> {
+    open().ignore_then(inner).then_ignore(close())
+}
+
+// Like `between`, for a pair of delimiter tokens. A missing `close` is
+// reported along with where `open` was, as in "expected RightBrace to close
+// the LeftBrace at line 3, column 1", with a note pointing at `open`, and
+// `inner`'s value is still returned.
+pub fn open_close<S, T>(open: <S::Token as Token>::TokenType, close: <S::Token as Token>::TokenType, inner: Parser<S, T>)
+-> Parser<S, T>
+where S: 'static + Scanner,
+      T: 'static,
+      <S::Token as Token>::TokenType: fmt::Debug {
+    let missing_open = format!("expected {:?}", open);
+    let missing_close = format!("expected {:?} to close the {:?}", close, open);
+    current_position() >> move |opened: S::Position|
+    consume(open, missing_open) >> move |_|
+    inner >> move |value|
+    current_position() >> move |position|
+    matches(close) >> move |closed|
+    if closed {
+        Parser::result(value)
+    } else {
+        let opened: Position = opened.into();
+        let error = ParseError::new(format!("{} at {}", missing_close, opened))
+            .at(position)
+            .note(opened, "opened here");
+        Parser::fail(value, error)
+    }
+}
+
+// Runs `a` then `b` and combines their values with `f`, keeping the errors
+// of both like `>>`
+pub fn map2<S, A, B, C, F>(a: Parser<S, A>, b: Parser<S, B>, f: F) -> Parser<S, C>
+where S: 'static + Scanner,
+      B: 'static,
+      C: 'static,
+      F: 'static + FnOnce(A, B) -> C {
+    a >> move |a| b.map(move |b| f(a, b))
+}
+
+// Like `map2`, for three parsers
+pub fn map3<S, A, B, C, D, F>(a: Parser<S, A>, b: Parser<S, B>, c: Parser<S, C>, f: F) -> Parser<S, D>
+where S: 'static + Scanner,
+      B: 'static,
+      C: 'static,
+      D: 'static,
+      F: 'static + FnOnce(A, B, C) -> D {
+    map2(a, b, |a, b| (a, b)) >> move |(a, b)| c.map(move |c| f(a, b, c))
+}
+
+// Parses `first`, `sep` and `second` in order, keeping both values but not
+// the separator's, as in `key: value`. Errors from all three are kept.
+pub fn separated_pair<S, A, Sep, B>(first: Parser<S, A>, sep: Parser<S, Sep>, second: Parser<S, B>) -> Parser<S, (A, B)>
+where S: 'static + Scanner,
+      Sep: 'static,
+      B: 'static {
+    map3(first, sep, second, |a, _, b| (a, b))
+}
+
+// Parses one or more operands separated by operators, combining them from
+// the left, so `1 - 2 - 3` is `(1 - 2) - 3`. Each operator parser yields the
+// function that combines its two operands.
+pub fn chainl1<S, T, F, O>(operand: F, op: O) -> Parser<S, T>
+where S: 'static + Scanner,
+      T: 'static,
+      F: 'static + Fn() -> Parser<S, T>,
+      O: 'static + Fn() -> Parser<S, Box<dyn Fn(T, T) -> T>> {
+    let operand = Rc::new(operand);
+    let first = operand();
+    fold(first, move || {
+        let operand = Rc::clone(&operand);
+        op() >> move |f| operand().map(move |b| (f, b))
+    }, |a, (f, b)| f(a, b))
+}
+
+// Like `chainl1`, but combining from the right, so `2 ^ 3 ^ 2` is
+// `2 ^ (3 ^ 2)`. The operands are collected first and then combined in a
+// loop, so long chains don't grow the stack.
+pub fn chainr1<S, T, F, O>(operand: F, op: O) -> Parser<S, T>
+where S: 'static + Scanner,
+      T: 'static,
+      F: 'static + Fn() -> Parser<S, T>,
+      O: 'static + Fn() -> Parser<S, Box<dyn Fn(T, T) -> T>> {
+    let operand = Rc::new(operand);
+    let first = operand();
+    first >> move |first|
+    many(move || {
+        let operand = Rc::clone(&operand);
+        op() >> move |f| operand().map(move |b| (f, b))
+    }) >> move |rest| {
+        let (ops, mut operands): (Vec<_>, Vec<_>) = rest.into_iter().unzip();
+        operands.insert(0, first);
+        let mut acc = operands.pop().expect("there is always a first operand");
+        for f in ops.into_iter().rev() {
+            let left = operands.pop().expect("every operator has a left operand");
+            acc = f(left, acc);
+        }
+        Parser::result(acc)
+    }
+}
+
+// Parses zero or more `item`s separated by `sep`, discarding the separators.
+// Items and separators are matched the same way as in `many`. A separator must
+// be followed by an item, so a trailing separator is an error.
+pub fn separated_by<S, T, U: 'static, F, G>(item: F, sep: G) -> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, T>,
+      G: 'static + Fn() -> Parser<S, U> {
+    separated(item, sep, false, false)
+}
+
+// Like `separated_by`, but at least one item is required
+pub fn sep_by1<S, T, U: 'static, F, G>(item: F, sep: G) -> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, T>,
+      G: 'static + Fn() -> Parser<S, U> {
+    separated(item, sep, true, false)
+}
+
+// Like `separated_by`, but the last item may be followed by a separator, as
+// in `f(a, b,)`
+pub fn sep_end_by<S, T, U: 'static, F, G>(item: F, sep: G) -> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, T>,
+      G: 'static + Fn() -> Parser<S, U> {
+    separated(item, sep, false, true)
+}
+
+// Parses `open`, items separated by `sep`, then `close`, as in an argument
+// list or an array literal. The list may be empty, and `allow_trailing`
+// decides whether a separator may come right before `close`, as in `[a, b,]`.
+pub fn delimited_list<S, O, T, U: 'static, C, FO, F, G, FC>(open: FO, item: F, sep: G, close: FC, allow_trailing: bool)
+-> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      O: 'static,
+      C: 'static,
+      FO: Fn() -> Parser<S, O>,
+      F: 'static + Fn() -> Parser<S, T>,
+      G: 'static + Fn() -> Parser<S, U>,
+      FC: Fn() -> Parser<S, C> {
+    between(open, separated(item, sep, false, allow_trailing), close)
+}
+
+fn separated<S, T, U: 'static, F, G>(item: F, sep: G, at_least_one: bool, allow_trailing: bool)
+-> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<S, T>,
+      G: 'static + Fn() -> Parser<S, U> {
+    Parser::primitive(move |scanner: S| {
+        let mut items = vec![];
+        let mut warnings = vec![];
+        let mut scanner = match try_parse(item(), scanner) {
+            Attempt::Matched(value, s, mut w) => {
+                items.push(value);
+                warnings.append(&mut w);
+                s
+            }
+            Attempt::NoMatch(s) if at_least_one => {
+                return (items, s, vec![ParseError::new("expected at least one")]);
+            }
+            Attempt::NoMatch(s) => return (items, s, vec![]),
+            Attempt::Failed(s, errors) => return (items, s, errors)
+        };
+        
+        loop {
+            scanner = match try_parse(sep(), scanner) {
+                Attempt::Matched(_, s, mut w) => {
+                    warnings.append(&mut w);
+                    s
+                }
+                Attempt::NoMatch(s) => return (items, s, warnings),
+                Attempt::Failed(s, mut errors) => {
+                    warnings.append(&mut errors);
+                    return (items, s, warnings);
+                }
+            };
+            scanner = match try_parse(item(), scanner) {
+                Attempt::Matched(value, s, mut w) => {
+                    items.push(value);
+                    warnings.append(&mut w);
+                    s
+                }
+                Attempt::NoMatch(s) if allow_trailing => return (items, s, warnings),
+                Attempt::NoMatch(s) => {
+                    warnings.push(ParseError::new("expected an item after separator"));
+                    return (items, s, warnings);
+                }
+                Attempt::Failed(s, mut errors) => {
+                    warnings.append(&mut errors);
+                    return (items, s, warnings);
+                }
+            };
+        }
+    })
+}
+
+// Tries `first` and then each of `rest` in order from the same starting
+// point, committing to the first one that parses without errors or fails after
+// a `cut`. If every alternative fails, the value, scanner and errors of the
+// last attempt are returned.
+pub fn choice<S, T>(first: Box<dyn Fn() -> Parser<S, T>>, rest: Vec<Box<dyn Fn() -> Parser<S, T>>>) -> Parser<S, T>
+where S: 'static + Scanner {
+    Parser::primitive(move |mut scanner: S| {
+        for (i, alternative) in std::iter::once(&first).chain(&rest).enumerate() {
+            let checkpoint = scanner.checkpoint();
+            let (value, s, errors) = alternative().evaluate(scanner);
+            if !has_errors(&errors) || is_cut(&errors) || i == rest.len() {
+                return (value, s, errors);
+            }
+            scanner = s.restore(checkpoint);
+        }
+        unreachable!("the last alternative always returns")
+    })
+}
+
+// Defers building the parser until it is run. This is a bind, so a grammar
+// that nests through `lazy` is evaluated without growing the stack.
+pub fn lazy<S, T, F>(factory: F) -> Parser<S, T>
+where S: 'static + Scanner,
+      T: 'static,
+      F: 'static + FnOnce() -> Parser<S, T> {
+    Parser::result(()) >> move |_| factory()
+}
+
+// Builds a self-referential parser. `builder` is given a factory for the
+// parser being defined; each parser it makes is only built when it is run, so
+// the definition doesn't recurse forever.
+pub fn recursive<S, T, F>(builder: F) -> Parser<S, T>
+where S: 'static + Scanner,
+      T: 'static,
+      F: 'static + Fn(Rc<dyn Fn() -> Parser<S, T>>) -> Parser<S, T> {
+    fn factory<S, T, F>(builder: Rc<F>) -> Rc<dyn Fn() -> Parser<S, T>>
+    where S: 'static + Scanner,
+          T: 'static,
+          F: 'static + Fn(Rc<dyn Fn() -> Parser<S, T>>) -> Parser<S, T> {
+        Rc::new(move || {
+            let builder = Rc::clone(&builder);
+            lazy(move || builder(factory(Rc::clone(&builder))))
+        })
+    }
+    factory(Rc::new(builder))()
+}
+
+// The results of a memoized parser by the position it started at: its value,
+// the position it ended at, and its errors
+type Memo<P, T> = HashMap<Position, (T, P, Vec<ParseError>)>;
+
+// Packrat memoization: runs the parser `grammar` builds, giving it a factory
+// for `rule` whose parsers share a cache of results keyed by the position
+// they started at, so trying the rule at the same position again, as
+// overlapping alternatives do, reuses the first result instead of reparsing.
+// A reused result is replayed by consuming the same tokens from the scanner
+// as it is now, so state such as scopes is kept as it is. Any other change
+// `rule` makes to the scanner isn't replayed, so only rules whose result
+// depends on nothing but the tokens should be memoized. The cache is made
+// afresh each time the parser runs.
+pub fn memoized<S, T, U, F, G>(rule: F, grammar: G) -> Parser<S, U>
+where S: 'static + Scanner,
+      T: 'static + Clone,
+      U: 'static,
+      F: 'static + Fn() -> Parser<S, T>,
+      G: 'static + FnOnce(Rc<dyn Fn() -> Parser<S, T>>) -> Parser<S, U> {
+    lazy(move || {
+        let cache = Rc::new(RefCell::new(Memo::<S::Position, T>::new()));
+        let rule = Rc::new(rule);
+        grammar(Rc::new(move || {
+            let cache = Rc::clone(&cache);
+            let rule = Rc::clone(&rule);
+            Parser::primitive(move |mut scanner: S| {
+                let start: Position = scanner.position().into();
+                let hit = cache.borrow().get(&start).cloned();
+                if let Some((value, end, errors)) = hit {
+                    while scanner.position() != end && !scanner.is_finished() {
+                        scanner = scanner.scan_token();
+                    }
+                    return (value, scanner, errors);
+                }
+                let (value, scanner, errors) = rule().evaluate(scanner);
+                cache.borrow_mut().insert(start, (value.clone(), scanner.position(), errors.clone()));
+                (value, scanner, errors)
+            })
+        }))
+    })
+}
+
+// Like `recursive`, but nesting more than `max_depth` levels deep fails with
+// "maximum nesting depth exceeded" instead of running on, so pathological
+// input such as thousands of open parentheses is rejected cleanly. The error
+// is cut and fatal, and the value is `T::default()`.
+pub fn recursive_limited<S, T, F>(max_depth: usize, builder: F) -> Parser<S, T>
+where S: 'static + Scanner,
+      T: 'static + Default,
+      F: 'static + Fn(Rc<dyn Fn() -> Parser<S, T>>) -> Parser<S, T> {
+    let depth = Rc::new(Cell::new(0));
+    let builder = Rc::new(builder);
+    recursive(move |inner| {
+        let depth = Rc::clone(&depth);
+        let builder = Rc::clone(&builder);
+        current_position() >> move |position|
+        if depth.get() >= max_depth {
+            let error = ParseError::new("maximum nesting depth exceeded").at(position).cut().fatal();
+            Parser::fail(T::default(), error)
+        } else {
+            depth.set(depth.get() + 1);
+            builder(inner).map(move |value| {
+                depth.set(depth.get() - 1);
+                value
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{render_error, ParseErrors};
+    use crate::parser::basic_functions::{advance, consume, current_position, declare, lookup, matches, peek, previous, take_while};
+    use crate::parser::basic_functions::tests::{TestScanner, TestToken, TokenType};
+    use crate::scanner::Token;
+    use crate::scanner::scoped::ScopedScanner;
+    use crate::scanner::slice::SliceScanner;
+    use crate::scanner::string::{CharToken, StringScanner};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum NumType {
+        Number,
+        Plus,
+        Minus,
+        End
+    }
+    
+    #[derive(Debug, Clone)]
+    struct NumToken(NumType, i32);
+    
+    impl Token for NumToken {
+        type TokenType = NumType;
+        fn t_type(&self) -> NumType {
+            self.0
+        }
+    }
+    
+    type NumScanner = SliceScanner<NumToken>;
+    
+    // Scans source like "1 + 2 - 3"
+    fn num_scanner(source: &str) -> NumScanner {
+        let tokens = source.split_whitespace().map(|word| match word {
+            "+" => NumToken(NumType::Plus, 0),
+            "-" => NumToken(NumType::Minus, 0),
+            number => NumToken(NumType::Number, number.parse().unwrap())
+        }).collect();
+        SliceScanner::new(tokens, NumToken(NumType::End, 0))
+    }
+    
+    fn number() -> Parser<NumScanner, i32> {
+        consume(NumType::Number, "expected number".to_string()).map(|token: Rc<NumToken>| token.1)
+    }
+    
+    fn subtract() -> Parser<NumScanner, Box<dyn Fn(i32, i32) -> i32>> {
+        consume(NumType::Minus, "expected -".to_string()) >> |_|
+        Parser::result(Box::new(|a, b| a - b) as Box<dyn Fn(i32, i32) -> i32>)
+    }
+    
+    type TestParser<T> = Parser<TestScanner, T>;
+
+    fn token_a() -> Parser<TestScanner, TokenType> {
+        matches(TokenType::A).if_else(
+            previous().map(|token: Rc<TestToken>| token.t_type()),
+            Parser::error(TokenType::None, "expected A".to_string())
+        )
+    }
+
+    fn scanner(types: Vec<TokenType>) -> TestScanner {
+        TestScanner::new(types.into_iter().map(TestToken).collect())
+    }
+
+    fn rest() -> Parser<TestScanner, TokenType> {
+        peek().map(|token: Rc<TestToken>| token.t_type())
+    }
+
+    #[test]
+    fn many_test() {
+        let parser = many(token_a) >> |items| rest().map(move |next| (items, next));
+        assert_eq!(Ok((vec![TokenType::A, TokenType::A], TokenType::B)),
+                   parser.run(scanner(vec![TokenType::A, TokenType::A, TokenType::B])));
+
+        assert_eq!(Ok(vec![]), many(token_a).run(scanner(vec![TokenType::B])));
+        assert_eq!(Ok(vec![]), many(token_a).run(scanner(vec![])));
+        
+        let parser = many(|| token_a() >> |t| Parser::warn(t, "deprecated".to_string()));
+        assert_eq!((Ok(vec![TokenType::A, TokenType::A]), vec![ParseError::warning("deprecated"); 2]),
+                   parser.run_with_diagnostics(scanner(vec![TokenType::A, TokenType::A])));
+
+        // A parser that never consumes ends the loop instead of spinning
+        assert_eq!(Ok(vec![]), many(|| matches(TokenType::B)).run(scanner(vec![TokenType::A])));
+
+        // Errors after consuming input are surfaced
+        let pair = || advance() >> |_| token_a();
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected A")])),
+                   many(pair).run(scanner(vec![TokenType::B, TokenType::A, TokenType::B, TokenType::B])));
+    }
+
+    #[test]
+    fn count_test() {
+        let parser = count(token_a) >> |n| rest().map(move |next| (n, next));
+        assert_eq!(Ok((3, TokenType::B)),
+                   parser.run(scanner(vec![TokenType::A, TokenType::A, TokenType::A, TokenType::B])));
+        assert_eq!(Ok(0), count(token_a).run(scanner(vec![TokenType::B])));
+        
+        // A parser that never consumes ends the loop instead of spinning
+        assert_eq!(Ok(0), count(|| matches(TokenType::B)).run(scanner(vec![TokenType::A])));
+    }
+
+    #[test]
+    fn many1_test() {
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected at least one")])), many1(token_a).run(scanner(vec![TokenType::B])));
+        assert_eq!(Ok(vec![TokenType::A, TokenType::A]), many1(token_a).run(scanner(vec![TokenType::A, TokenType::A])));
+        assert_eq!(Ok(vec![TokenType::A]), many1(token_a).run(scanner(vec![TokenType::A, TokenType::B])));
+    }
+
+    #[test]
+    fn separated_by_test() {
+        let list = || separated_by(token_a, || matches(TokenType::Comma));
+        
+        assert_eq!(Ok(vec![]), list().run(scanner(vec![])));
+        assert_eq!(Ok(vec![]), list().run(scanner(vec![TokenType::B])));
+        assert_eq!(Ok(vec![TokenType::A]), list().run(scanner(vec![TokenType::A])));
+        
+        let parser = list() >> |items| rest().map(move |next| (items, next));
+        assert_eq!(Ok((vec![TokenType::A, TokenType::A], TokenType::B)),
+                   parser.run(scanner(vec![TokenType::A, TokenType::Comma, TokenType::A, TokenType::B])));
+        
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected an item after separator")])),
+                   list().run(scanner(vec![TokenType::A, TokenType::Comma, TokenType::B])));
+    }
+
+    #[test]
+    fn sep_by1_test() {
+        let list = || sep_by1(token_a, || matches(TokenType::Comma));
+        
+        assert_eq!(Ok(vec![TokenType::A]), list().run(scanner(vec![TokenType::A])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected at least one")])), list().run(scanner(vec![TokenType::B])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected an item after separator")])),
+                   list().run(scanner(vec![TokenType::A, TokenType::Comma])));
+    }
+
+    #[test]
+    fn sep_end_by_test() {
+        let list = || sep_end_by(token_a, || matches(TokenType::Comma));
+        
+        assert_eq!(Ok(vec![]), list().run(scanner(vec![])));
+        assert_eq!(Ok(vec![TokenType::A]), list().run(scanner(vec![TokenType::A, TokenType::Comma])));
+        
+        // The trailing separator is consumed
+        let parser = list() >> |items| rest().map(move |next| (items, next));
+        assert_eq!(Ok((vec![TokenType::A, TokenType::A], TokenType::B)),
+                   parser.run(scanner(vec![TokenType::A, TokenType::Comma, TokenType::A, TokenType::Comma, TokenType::B])));
+    }
+
+    #[test]
+    fn choice_test() {
+        let parser = choice(Box::new(|| matches(TokenType::A) >> |_| token_a()), vec![
+            Box::new(|| matches(TokenType::A) >> |_| Parser::result(TokenType::B)),
+            Box::new(|| Parser::result(TokenType::None))
+        ]);
+        
+        // The first alternative consumes an `A` before failing, which is undone
+        assert_eq!(Ok((TokenType::B, TokenType::B)),
+            (parser >> |value| rest().map(move |next| (value, next)))
+            .run(scanner(vec![TokenType::A, TokenType::B])));
+        
+        let parser = choice(Box::new(|| Parser::error(1, "first".to_string())), vec![
+            Box::new(|| Parser::error(2, "second".to_string()))
+        ]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("second")])), parser.run(scanner(vec![])));
+        
+        // A single alternative is returned as it is
+        let parser = choice(Box::new(|| Parser::error(1, "only".to_string())), vec![]);
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("only")])), parser.run(scanner(vec![TokenType::A])));
+    }
+
+    #[test]
+    fn repeat_n_test() {
+        assert_eq!(Ok(vec![TokenType::A, TokenType::A]),
+                   repeat_n(2, token_a).run(scanner(vec![TokenType::A, TokenType::A])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected A")])), repeat_n(2, token_a).run(scanner(vec![TokenType::A])));
+        
+        let parser = repeat_n(0, token_a) >> |items| current_position().map(move |position| (items, position));
+        assert_eq!(Ok((vec![], 0)), parser.run(scanner(vec![TokenType::A])));
+    }
+
+    #[test]
+    fn fold_test() {
+        let sum = || fold(number(), || consume(NumType::Plus, "expected +".to_string()) >> |_| number(), |a, b| a + b);
+        assert_eq!(Ok(6), sum().run(num_scanner("1 + 2 + 3")));
+        assert_eq!(Ok(1), sum().run(num_scanner("1")));
+        
+        // Stops at the first step that doesn't match
+        let parser = sum() >> |total| current_position().map(move |position| (total, position));
+        assert_eq!(Ok((3, 3)), parser.run(num_scanner("1 + 2 - 3")));
+        
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected number").at(2)])), sum().run(num_scanner("1 + -")));
+    }
+
+    #[test]
+    fn map2_test() {
+        assert_eq!(Ok(3), map2(TestParser::result(1), TestParser::result(2), |a, b| a + b).run(scanner(vec![])));
+        
+        let (value, _, errors) = map2(
+            TestParser::error(1, "left".to_string()),
+            TestParser::error(2, "right".to_string()),
+            |a, b| a + b
+        ).evaluate(scanner(vec![]));
+        assert_eq!(3, value);
+        assert_eq!(vec![ParseError::new("left"), ParseError::new("right")], errors);
+        
+        let triple = map3(token_a(), token_a(), rest(), |a, b, c| vec![a, b, c]);
+        assert_eq!(Ok(vec![TokenType::A, TokenType::A, TokenType::B]),
+                   triple.run(scanner(vec![TokenType::A, TokenType::A, TokenType::B])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected A")])),
+                   map3(token_a(), token_a(), rest(), |a, _, _| a).run(scanner(vec![TokenType::A, TokenType::B])));
+    }
+
+    #[test]
+    fn separated_pair_test() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum KvType {
+            Ident,
+            Colon,
+            Number,
+            End
+        }
+        
+        #[derive(Debug, Clone)]
+        struct KvToken(KvType, &'static str);
+        
+        impl Token for KvToken {
+            type TokenType = KvType;
+            fn t_type(&self) -> KvType {
+                self.0
+            }
+        }
+        
+        let entry = || separated_pair(
+            consume(KvType::Ident, "expected name".to_string()).map(|token: Rc<KvToken>| token.1),
+            consume(KvType::Colon, "expected :".to_string()),
+            consume(KvType::Number, "expected value".to_string()).map(|token: Rc<KvToken>| token.1.parse::<i32>().unwrap_or(0))
+        );
+        let scanner = |tokens| SliceScanner::new(tokens, KvToken(KvType::End, ""));
+        
+        assert_eq!(Ok(("width", 80)),
+                   entry().run(scanner(vec![KvToken(KvType::Ident, "width"), KvToken(KvType::Colon, ":"), KvToken(KvType::Number, "80")])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected :").at(1), ParseError::new("expected value").at(1)])),
+                   entry().run(scanner(vec![KvToken(KvType::Ident, "width"), KvToken(KvType::Ident, "height")])));
+    }
+
+    #[test]
+    fn delimited_list_test() {
+        let list = |allow_trailing| delimited_list(
+            || consume(TokenType::LeftParen, "expected (".to_string()),
+            token_a,
+            || matches(TokenType::Comma),
+            || consume(TokenType::RightParen, "expected )".to_string()),
+            allow_trailing
+        );
+        let trailing = || scanner(vec![TokenType::LeftParen, TokenType::A, TokenType::Comma, TokenType::RightParen]);
+        
+        assert_eq!(Ok(vec![]), list(false).run(scanner(vec![TokenType::LeftParen, TokenType::RightParen])));
+        assert_eq!(Ok(vec![TokenType::A, TokenType::A]), list(false).run(scanner(vec![
+            TokenType::LeftParen, TokenType::A, TokenType::Comma, TokenType::A, TokenType::RightParen
+        ])));
+        assert_eq!(Ok(vec![TokenType::A]), list(true).run(trailing()));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected an item after separator")])), list(false).run(trailing()));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected )").at(2)])),
+                   list(true).run(scanner(vec![TokenType::LeftParen, TokenType::A])));
+    }
+
+    #[test]
+    fn zip_longest_test() {
+        // Each `A` may be followed by a `B` as its default
+        let default = || consume(TokenType::B, "expected B".to_string()).map(|token: Rc<TestToken>| token.t_type());
+        let params = || zip_longest(token_a, default);
+        
+        assert_eq!(Ok(vec![(TokenType::A, Some(TokenType::B)), (TokenType::A, Some(TokenType::B))]),
+                   params().run(scanner(vec![TokenType::A, TokenType::B, TokenType::A, TokenType::B])));
+        
+        // Once a default is missing, the rest have none
+        let parser = params() >> |pairs| rest().map(move |next| (pairs, next));
+        assert_eq!(Ok((vec![(TokenType::A, Some(TokenType::B)), (TokenType::A, None), (TokenType::A, None)], TokenType::RightParen)),
+                   parser.run(scanner(vec![TokenType::A, TokenType::B, TokenType::A, TokenType::A, TokenType::RightParen])));
+        
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected at most 1 item, found 3").at(2)])),
+                   params().run(scanner(vec![TokenType::A, TokenType::B, TokenType::B, TokenType::B])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected at most 2 items, found 3").at(4)])),
+                   params().run(scanner(vec![TokenType::A, TokenType::B, TokenType::A, TokenType::B, TokenType::B])));
+    }
+    
+    #[test]
+    fn between_test() {
+        let parens = || between(
+            || consume(TokenType::LeftParen, "expected (".to_string()),
+            token_a(),
+            || consume(TokenType::RightParen, "expected )".to_string())
+        );
+        
+        assert_eq!(Ok(TokenType::A),
+                   parens().run(scanner(vec![TokenType::LeftParen, TokenType::A, TokenType::RightParen])));
+        
+        let (value, _, errors) = parens().evaluate(scanner(vec![TokenType::LeftParen, TokenType::A]));
+        assert_eq!(TokenType::A, value);
+        assert_eq!(vec![ParseError::new("expected )").at(2)], errors);
+    }
+
+    #[test]
+    fn open_close_test() {
+        let parens = || open_close(TokenType::LeftParen, TokenType::RightParen, token_a());
+        
+        assert_eq!(Ok(TokenType::A),
+                   parens().run(scanner(vec![TokenType::LeftParen, TokenType::A, TokenType::RightParen])));
+        
+        // The error is where the close was expected, and names where the open was
+        let (value, _, errors) = (advance() >> move |_| parens())
+            .evaluate(scanner(vec![TokenType::B, TokenType::LeftParen, TokenType::A, TokenType::RightBrace]));
+        assert_eq!(TokenType::A, value);
+        assert_eq!(vec![ParseError::new("expected RightParen to close the LeftParen at position 1").at(3).note(1, "opened here")],
+                   errors);
+        
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected LeftParen").at(0)])),
+                   parens().run(scanner(vec![TokenType::A, TokenType::RightParen])));
+
+        // Both the error and where the delimiter was opened are shown
+        let source = "f {\n  a\n";
+        let block = open_close('{', '}', take_while(|token: &CharToken| token.ch != '}'));
+        let errors = (advance() >> move |_| matches(' ') >> move |_| block)
+            .run(StringScanner::new(source)).unwrap_err().0;
+        assert_eq!(
+            "expected '}' to close the '{' at line 1, column 3 at line 3, column 1\n3 | \n  | ^\n\
+             note: opened here at line 1, column 3\n1 | f {\n  |   ^",
+            render_error(source, &errors[0])
+        );
+    }
+    
+    #[test]
+    fn chainl1_test() {
+        assert_eq!(Ok(-4), chainl1(number, subtract).run(num_scanner("1 - 2 - 3")));
+        assert_eq!(Ok(5), chainl1(number, subtract).run(num_scanner("5")));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected number").at(2)])),
+                   chainl1(number, subtract).run(num_scanner("1 - -")));
+    }
+
+    #[test]
+    fn long_input_test() {
+        // Repetition loops over the scanner, so long inputs don't grow the stack
+        let count = 100_000;
+        let numbers = vec!["1"; count].join(" ");
+        assert_eq!(Ok(count), many(number).map(|items: Vec<i32>| items.len()).run(num_scanner(&numbers)));
+        assert_eq!(Ok(count), many1(number).map(|items: Vec<i32>| items.len()).run(num_scanner(&numbers)));
+        
+        let tokens = take_while(|token: &NumToken| token.0 == NumType::Number);
+        assert_eq!(Ok(count), tokens.map(|items: Vec<Rc<NumToken>>| items.len()).run(num_scanner(&numbers)));
+        
+        let list = separated_by(number, || consume(NumType::Minus, "expected -".to_string()));
+        let source = vec!["1"; count].join(" - ");
+        assert_eq!(Ok(count), list.map(|items: Vec<i32>| items.len()).run(num_scanner(&source)));
+    }
+
+    #[test]
+    fn chainr1_test() {
+        assert_eq!(Ok(2), chainr1(number, subtract).run(num_scanner("1 - 2 - 3")));
+        assert_ne!(chainl1(number, subtract).run(num_scanner("1 - 2 - 3")),
+                   chainr1(number, subtract).run(num_scanner("1 - 2 - 3")));
+        assert_eq!(Ok(5), chainr1(number, subtract).run(num_scanner("5")));
+        
+        let source = vec!["1"; 10_000].join(" - ");
+        assert_eq!(Ok(0), chainr1(number, subtract).run(num_scanner(&source)));
+    }
+
+    #[test]
+    fn recursive_test() {
+        // atom = A | "(" atom ")", returning how deeply the A is nested
+        let atom = || recursive(|atom: Rc<dyn Fn() -> TestParser<usize>>|
+            matches(TokenType::LeftParen).if_else(
+                atom() >> |depth|
+                consume(TokenType::RightParen, "expected )".to_string()) >> move |_|
+                Parser::result(depth + 1),
+                token_a().map(|_| 0)
+            )
+        );
+        
+        assert_eq!(Ok(0), atom().run(scanner(vec![TokenType::A])));
+        assert_eq!(Ok(2), atom().run(scanner(vec![
+            TokenType::LeftParen, TokenType::LeftParen, TokenType::A, TokenType::RightParen, TokenType::RightParen
+        ])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected )").at(2)])),
+                   atom().run(scanner(vec![TokenType::LeftParen, TokenType::A])));
+    }
+
+    #[test]
+    fn memoized_test() {
+        let runs = Rc::new(Cell::new(0));
+        let counted = |runs: &Rc<Cell<usize>>| {
+            let counter = Rc::clone(runs);
+            move || {
+                counter.set(counter.get() + 1);
+                token_a()
+            }
+        };
+        
+                // Both alternatives start with `item` at the same position
+        let pair_or_single = || memoized(counted(&runs), |item: Rc<dyn Fn() -> TestParser<TokenType>>| {
+            let second = Rc::clone(&item);
+            (item() >> |_| consume(TokenType::Comma, "expected ,".to_string()).map(|_| "pair"))
+            | second().map(|_| "single")
+        });
+        assert_eq!(Ok(("single", TokenType::B)),
+                   (pair_or_single() + rest()).run(scanner(vec![TokenType::A, TokenType::B])));
+        assert_eq!(1, runs.get());
+        
+        // Each run starts with an empty cache
+        assert_eq!(Ok("single"), pair_or_single().run(scanner(vec![TokenType::A, TokenType::B])));
+                assert_eq!(2, runs.get());
+
+        // A different position is parsed afresh
+        let parser = memoized(counted(&runs), |item: Rc<dyn Fn() -> TestParser<TokenType>>| {
+            let second = Rc::clone(&item);
+            item() >> move |_| second()
+        });
+        assert_eq!(Ok(TokenType::A), parser.run(scanner(vec![TokenType::A, TokenType::A])));
+        assert_eq!(4, runs.get());
+    }
+
+    #[test]
+    fn memoized_scope_test() {
+        type Scoped = ScopedScanner<TestScanner, i32>;
+        let item = || consume(TokenType::A, "expected A".to_string());
+
+        // Reusing `item`'s result doesn't bring back the name the first
+        // alternative declared before it was undone
+        let parser = memoized(item, |item: Rc<dyn Fn() -> Parser<Scoped, Rc<TestToken>>>| {
+            let second = Rc::clone(&item);
+            (declare("x".to_string(), 1) >> move |_|
+             item() >> |_|
+             consume(TokenType::Comma, "expected ,".to_string()).map(|_| None))
+            | (second() >> |_| lookup("x".to_string()))
+        });
+        assert_eq!(Ok(None), parser.run(ScopedScanner::new(scanner(vec![TokenType::A, TokenType::B]))));
+
+        // and a name declared before the reused result is kept
+        let parser = memoized(item, |item: Rc<dyn Fn() -> Parser<Scoped, Rc<TestToken>>>| {
+            let second = Rc::clone(&item);
+            (item() >> |_| consume(TokenType::Comma, "expected ,".to_string()).map(|_| None))
+            | (declare("y".to_string(), 2) >> move |_| second() >> |_| lookup("y".to_string()))
+        });
+        assert_eq!(Ok(Some(2)), parser.run(ScopedScanner::new(scanner(vec![TokenType::A, TokenType::B]))));
+    }
+
+    #[test]
+    fn recursive_limited_test() {
+        let atom = || recursive_limited(3, |atom: Rc<dyn Fn() -> TestParser<usize>>|
+            matches(TokenType::LeftParen).if_else(
+                atom() >> |depth|
+                consume(TokenType::RightParen, "expected )".to_string()) >> move |_|
+                Parser::result(depth + 1),
+                token_a().map(|_| 0)
+            )
+        );
+        let nested = |depth| {
+            let mut types = vec![TokenType::LeftParen; depth];
+            types.push(TokenType::A);
+            types.extend(vec![TokenType::RightParen; depth]);
+            scanner(types)
+        };
+        
+        assert_eq!(Ok(2), atom().run(nested(2)));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("maximum nesting depth exceeded").at(3).cut().fatal()])),
+                   atom().run(nested(3)));
+        assert!(atom().run(nested(100_000)).is_err());
+        
+        // The depth goes back down as nested parsers finish
+        let pair = recursive_limited(2, |pair: Rc<dyn Fn() -> TestParser<usize>>|
+            matches(TokenType::LeftParen).if_else(
+                pair() >> move |a|
+                pair() >> move |b|
+                consume(TokenType::RightParen, "expected )".to_string()) >> move |_|
+                Parser::result(a + b + 1),
+                token_a().map(|_| 0)
+            )
+        );
+        let source = vec![TokenType::LeftParen, TokenType::A, TokenType::A, TokenType::RightParen];
+        assert_eq!(Ok(1), pair.run(scanner(source)));
+    }
+
+    #[test]
+    fn many_till_test() {
+        let block = || many_till(token_a, || consume(TokenType::RightBrace, "expected }".to_string()));
+        
+        let parser = block() >> |items| rest().map(move |next| (items, next));
+        assert_eq!(Ok((vec![TokenType::A, TokenType::A], TokenType::B)),
+                   parser.run(scanner(vec![TokenType::A, TokenType::A, TokenType::RightBrace, TokenType::B])));
+        assert_eq!(Ok(vec![]), block().run(scanner(vec![TokenType::RightBrace])));
+        
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected terminator before end of input").at(2)])),
+                   block().run(scanner(vec![TokenType::A, TokenType::A])));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected A")])),
+                   block().run(scanner(vec![TokenType::A, TokenType::B, TokenType::RightBrace])));
+    }
+
+    #[test]
+    fn cut_test() {
+        fn statement(cut: bool) -> Parser<TestScanner, TokenType> {
+            let condition = || consume(TokenType::A, "expected condition".to_string()).map(|token: Rc<TestToken>| token.t_type());
+            choice(
+                Box::new(move || consume(TokenType::Let, "expected let".to_string()) >> move |_|
+                    if cut {
+                        condition().cut()
+                    } else {
+                        condition()
+                    }),
+                vec![Box::new(|| consume(TokenType::Fn, "expected statement".to_string()).map(|token: Rc<TestToken>| token.t_type()))]
+            )
+        }
+        let input = || scanner(vec![TokenType::Let, TokenType::B]);
+        
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected statement").at(0)])), statement(false).run(input()));
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected condition").at(1).cut()])), statement(true).run(input()));
+        assert_eq!(Ok(TokenType::A), statement(true).run(scanner(vec![TokenType::Let, TokenType::A])));
+        
+        let parser = consume(TokenType::Let, "expected let".to_string()).cut().map(|_| ()) | Parser::result(());
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected let").at(0).cut()])), parser.run(scanner(vec![TokenType::B])));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::error::{has_errors, ParseError, ParseErrors};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::scanner::string::StringScanner;
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+// The items parsed from a source, each with the byte range it was parsed
+// from, kept so that `reparse` can reuse them after an edit
+pub struct ParseCache<T> {
+    items: Vec<(T, Range<usize>)>,
+    reused: usize
+}
+
+impl<T> ParseCache<T> {
+    pub fn items(&self) -> Vec<&T> {
+        self.items.iter().map(|(item, _)| item).collect()
+    }
+
+    pub fn ranges(&self) -> Vec<Range<usize>> {
+        self.items.iter().map(|(_, range)| range.clone()).collect()
+    }
+
+    // How many of the items were taken from the previous parse rather than
+    // parsed again
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+}
+
+// A change to the source, in bytes: what was `start..old_end` is now
+// `start..new_end`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edit {
+    pub start: usize,
+    pub old_end: usize,
+    pub new_end: usize
+}
+
+// Parses all of `source` as a sequence of items. Each item must consume
+// something, along with any whitespace after it, so that the items cover the
+// source. The first item that fails to parse ends the parse with its errors;
+// warnings aren't kept.
+pub fn parse_items<T, F>(item: F, source: &str) -> Result<ParseCache<T>, ParseErrors>
+where T: 'static,
+      F: Fn() -> Parser<StringScanner, T> {
+    parse_reusing(item, source, HashMap::new())
+}
+
+// Parses `new_source`, the result of applying `edit` to the source of
+// `previous`, reusing the items of `previous` that the edit didn't touch.
+// Parsing starts over at the first damaged item and goes back to reusing
+// items as soon as it reaches the start of one that follows the edit. Items
+// are only reused if they are at least a byte away from the edit, so an item
+// may look one character past its end, but its parse must otherwise not
+// depend on what comes before or after it. Each item reused from after the
+// edit is passed to `shift` along with how many bytes the edit moved it, so
+// that any offsets it holds can be moved too.
+pub fn reparse<T, F, G>(previous: &ParseCache<T>, edit: Edit, new_source: &str, item: F, shift: G) -> Result<ParseCache<T>, ParseErrors>
+where T: 'static + Clone,
+      F: Fn() -> Parser<StringScanner, T>,
+      G: Fn(&mut T, isize) {
+    let delta = edit.new_end as isize - edit.old_end as isize;
+    let mut reusable = HashMap::new();
+    for (value, range) in &previous.items {
+        if range.end < edit.start {
+            reusable.insert(range.start, (value.clone(), range.end));
+        } else if range.start > edit.old_end {
+            let moved = |offset: usize| offset - edit.old_end + edit.new_end;
+            let mut value = value.clone();
+            shift(&mut value, delta);
+            reusable.insert(moved(range.start), (value, moved(range.end)));
+        }
+    }
+    parse_reusing(item, new_source, reusable)
+}
+
+// `reusable` holds the value and end of each item that can be reused, by
+// where it starts
+fn parse_reusing<T, F>(item: F, source: &str, mut reusable: HashMap<usize, (T, usize)>) -> Result<ParseCache<T>, ParseErrors>
+where T: 'static,
+      F: Fn() -> Parser<StringScanner, T> {
+    let mut scanner = StringScanner::new(source);
+    let mut items = vec![];
+    let mut reused = 0;
+    while !scanner.is_finished() {
+        let start = scanner.position().offset;
+        if let Some((value, end)) = reusable.remove(&start) {
+            while !scanner.is_finished() && scanner.position().offset < end {
+                scanner = scanner.scan_token();
+            }
+            items.push((value, start..end));
+            reused += 1;
+            continue;
+        }
+
+        let (value, s, errors) = item().evaluate(scanner);
+        if has_errors(&errors) {
+            return Err(ParseErrors(errors));
+        }
+        let end = s.position().offset;
+        if end == start {
+            return Err(ParseErrors(vec![ParseError::new("expected an item").at(s.position())]));
+        }
+        items.push((value, start..end));
+        scanner = s;
+    }
+    Ok(ParseCache { items, reused })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Position;
+    use crate::parser::basic_functions::{consume, matches, span_of, take_while};
+    use crate::scanner::string::CharToken;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // A statement is anything up to a `;`, followed by an optional line break
+    fn statement() -> Parser<StringScanner, String> {
+        take_while(|token: &CharToken| token.ch != ';') >> |chars: Vec<Rc<CharToken>>|
+        consume(';', "expected ;".to_string()) >> |_|
+        matches('\n') >> move |_|
+        Parser::result(chars.iter().map(|token| token.ch).collect())
+    }
+
+    // For items that hold no offsets
+    fn unshifted(_: &mut String, _: isize) {}
+
+    #[test]
+    fn reparse_test() {
+        let runs = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&runs);
+        let counted = move || {
+            counter.set(counter.get() + 1);
+            statement()
+        };
+
+        let source = "let a;\nlet b;\nlet c;\n";
+        let cache = parse_items(&counted, source).unwrap();
+        assert_eq!(vec!["let a", "let b", "let c"], cache.items());
+        assert_eq!(3, runs.get());
+
+        // Only the edited statement is parsed again
+        let edit = Edit { start: 11, old_end: 12, new_end: 14 };
+        let cache = reparse(&cache, edit, "let a;\nlet bbb;\nlet c;\n", &counted, unshifted).unwrap();
+        assert_eq!(vec!["let a", "let bbb", "let c"], cache.items());
+        assert_eq!(vec![0..7, 7..16, 16..23], cache.ranges());
+        assert_eq!(2, cache.reused());
+        assert_eq!(4, runs.get());
+
+        // Joining two statements reparses both
+        let edit = Edit { start: 14, old_end: 16, new_end: 14 };
+        let cache = reparse(&cache, edit, "let a;\nlet bbblet c;\n", &counted, unshifted).unwrap();
+        assert_eq!(vec!["let a", "let bbblet c"], cache.items());
+        assert_eq!(1, cache.reused());
+
+        let edit = Edit { start: 13, old_end: 21, new_end: 13 };
+        assert_eq!(Some(ParseErrors(vec![ParseError::new("expected ;").at(Position::new(13, 2, 7))])),
+                   reparse(&cache, edit, "let a;\nlet bb", &counted, unshifted).err());
+    }
+
+    #[test]
+    fn shift_test() {
+        // Each statement along with the offset it starts at
+        let spanned = || span_of(statement()).map(|(text, start, _)| (text, start.offset));
+        let shift = |item: &mut (String, usize), delta: isize| item.1 = (item.1 as isize + delta) as usize;
+
+        let cache = parse_items(spanned, "let a;\nlet b;\n").unwrap();
+        assert_eq!(vec![&("let a".to_string(), 0), &("let b".to_string(), 7)], cache.items());
+
+        // The reused statement after the edit has its offset moved with it
+        let edit = Edit { start: 5, old_end: 5, new_end: 7 };
+        let cache = reparse(&cache, edit, "let aaa;\nlet b;\n", spanned, shift).unwrap();
+        assert_eq!(vec![&("let aaa".to_string(), 0), &("let b".to_string(), 9)], cache.items());
+        assert_eq!(1, cache.reused());
+
+        // and so does one after an edit that shrinks the source
+        let edit = Edit { start: 4, old_end: 7, new_end: 4 };
+        let cache = reparse(&cache, edit, "let ;\nlet b;\n", spanned, shift).unwrap();
+        assert_eq!(vec![&("let ".to_string(), 0), &("let b".to_string(), 6)], cache.items());
+        assert_eq!(1, cache.reused());
+    }
+}
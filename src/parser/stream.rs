@@ -0,0 +1,218 @@
+use crate::error::{has_errors, ParseError, ParseErrors};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::scanner::bounded::BoundedScanner;
+
+// Pulls one item per call to `next`, stopping at the end of the input or
+// after the first item that fails to parse
+pub struct ItemStream<S, F> {
+    item: F,
+    scanner: Option<S>
+}
+
+pub fn into_stream_of_items<S, T: 'static, F>(item: F, scanner: S) -> ItemStream<S, F>
+where S: 'static + Scanner,
+      F: Fn() -> Parser<S, T> {
+    ItemStream {
+        item,
+        scanner: Some(scanner)
+    }
+}
+
+impl<S, T: 'static, F> Iterator for ItemStream<S, F>
+where S: 'static + Scanner,
+      F: Fn() -> Parser<S, T> {
+    type Item = Result<T, ParseErrors>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let scanner = self.scanner.take()?;
+        if scanner.is_finished() {
+            return None;
+        }
+
+        let (value, scanner, errors) = (self.item)().evaluate(scanner);
+        if !has_errors(&errors) {
+            self.scanner = Some(scanner);
+            Some(Ok(value))
+        } else {
+            Some(Err(ParseErrors(errors)))
+        }
+    }
+}
+
+// Like `ItemStream`, but after an item fails to parse the `recover` parser is
+// run (typically skipping to the next item boundary) and iteration carries
+// on. `recover` must consume input so that a broken item cannot be retried
+// forever.
+pub struct RecoveringItemStream<S, F, R> {
+    item: F,
+    recover: R,
+    scanner: Option<S>
+}
+
+pub fn into_recovering_stream<S, T: 'static, F, R>(item: F, recover: R, scanner: S)
+-> RecoveringItemStream<S, F, R>
+where S: 'static + Scanner,
+      F: Fn() -> Parser<S, T>,
+      R: Fn() -> Parser<S, ()> {
+    RecoveringItemStream {
+        item,
+        recover,
+        scanner: Some(scanner)
+    }
+}
+
+impl<S, T: 'static, F, R> Iterator for RecoveringItemStream<S, F, R>
+where S: 'static + Scanner,
+      F: Fn() -> Parser<S, T>,
+      R: Fn() -> Parser<S, ()> {
+    type Item = Result<T, ParseErrors>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let scanner = self.scanner.take()?;
+        if scanner.is_finished() {
+            return None;
+        }
+
+        let (value, scanner, errors) = (self.item)().evaluate(scanner);
+        if !has_errors(&errors) {
+            self.scanner = Some(scanner);
+            Some(Ok(value))
+        } else {
+            let (_, scanner, _) = (self.recover)().evaluate(scanner);
+            self.scanner = Some(scanner);
+            Some(Err(ParseErrors(errors)))
+        }
+    }
+}
+
+// Parses items until the end of the input, giving each item its own budget of
+// `max_tokens_per_item` tokens. An item that tries to read past its budget is
+// reported and parsing stops there.
+pub fn run_bounded<S, T: 'static, F>(max_tokens_per_item: usize, item: F) -> Parser<S, Vec<T>>
+where S: 'static + Scanner,
+      F: 'static + Fn() -> Parser<BoundedScanner<S>, T> {
+    Parser::primitive(move |mut scanner: S| {
+        let mut items = vec![];
+        while !scanner.is_finished() {
+            let bounded = BoundedScanner::new(scanner, max_tokens_per_item);
+            let (value, bounded, errors) = item().evaluate(bounded);
+            let exceeded = bounded.exceeded();
+            scanner = bounded.into_inner();
+
+            if exceeded {
+                let error = ParseError::new(format!("item exceeded the budget of {} tokens", max_tokens_per_item))
+                    .at(scanner.position());
+                return (items, scanner, vec![error]);
+            } else if has_errors(&errors) {
+                return (items, scanner, errors);
+            }
+            items.push(value);
+        }
+        (items, scanner, vec![])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::basic_functions::{advance, check, is_at_end, matches, recover_at_keywords};
+    use crate::parser::basic_functions::tests::{TestScanner, TestToken, TokenType};
+
+    #[test]
+    fn item_stream_test() {
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::b(), TestToken::a()]);
+        let items: Vec<_> = into_stream_of_items(advance, scanner)
+            .map(|item| item.map(|token| (*token).clone()))
+            .collect();
+        assert_eq!(vec![Ok(TestToken::a()), Ok(TestToken::b()), Ok(TestToken::a())], items);
+
+        let mut stream = into_stream_of_items(advance, TestScanner::new(vec![]));
+        assert_eq!(None, stream.next());
+    }
+
+    #[test]
+    fn item_stream_error_test() {
+        let scanner = TestScanner::new(vec![TestToken::a(), TestToken::a()]);
+        let mut stream = into_stream_of_items(|| Parser::error((), "bad item".to_string()), scanner);
+        assert_eq!(Some(Err(ParseErrors(vec![ParseError::new("bad item")]))), stream.next());
+        assert_eq!(None, stream.next());
+    }
+
+    #[test]
+    fn recovering_stream_test() {
+        // Each record is an `A` followed by a `;`
+        fn record() -> Parser<TestScanner, TokenType> {
+            matches(TokenType::A) >> |found|
+            matches(TokenType::Semicolon) >> move |terminated|
+            if found && terminated {
+                Parser::result(TokenType::A)
+            } else {
+                Parser::error(TokenType::None, "corrupt record".to_string())
+            }
+        }
+        fn recover() -> Parser<TestScanner, ()> {
+            recover_at_keywords(vec![TokenType::Semicolon]) >> |_|
+            matches(TokenType::Semicolon) >> |_|
+            Parser::result(())
+        }
+        
+        let scanner = TestScanner::new(vec![
+            TestToken::a(), TestToken(TokenType::Semicolon),
+            TestToken::b(), TestToken::b(), TestToken(TokenType::Semicolon),
+            TestToken::a(), TestToken(TokenType::Semicolon)
+        ]);
+        let records: Vec<_> = into_recovering_stream(record, recover, scanner).collect();
+        assert_eq!(vec![
+            Ok(TokenType::A),
+            Err(ParseErrors(vec![ParseError::new("corrupt record")])),
+            Ok(TokenType::A)
+        ], records);
+    }
+    
+    // An item is any number of `A`s terminated by a `B`
+    fn item<S>() -> Parser<S, usize>
+    where S: 'static + Scanner<Token = TestToken> {
+        matches(TokenType::B) >> |found|
+        if found {
+            Parser::result(1)
+        } else {
+            is_at_end() >> |at_end|
+            if at_end {
+                Parser::error(0, "unterminated item".to_string())
+            } else {
+                advance() >> |_|
+                item() >> |length|
+                Parser::result(length + 1)
+            }
+        }
+    }
+
+    #[test]
+    fn run_bounded_test() {
+        let scanner = TestScanner::new(vec![
+            TestToken::b(), TestToken::a(), TestToken::b(), TestToken::a(), TestToken::a(), TestToken::b()
+        ]);
+        assert_eq!(Ok(vec![1, 2, 3]), run_bounded(3, item).run(scanner));
+
+        let scanner = TestScanner::new(vec![
+            TestToken::a(), TestToken::b(), TestToken::a(), TestToken::a(), TestToken::a(), TestToken::b()
+        ]);
+        let parser = run_bounded(3, item) >> |items| {
+            assert_eq!(vec![2], items);
+            Parser::result(())
+        };
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("item exceeded the budget of 3 tokens").at(5)])), parser.run(scanner));
+    }
+
+    #[test]
+    fn run_bounded_exact_budget_test() {
+        let scanner = || TestScanner::new(vec![TestToken::a(), TestToken::a(), TestToken::b(), TestToken::b()]);
+        assert_eq!(Ok(vec![3, 1]), run_bounded(3, item).run(scanner()));
+
+        // Peeking at the token after the budget is allowed, as is trying to
+        // consume it in a lookahead that is backtracked
+        let peeking = || item().then_ignore(check(TokenType::B)).then_ignore(advance().lookahead());
+        assert_eq!(Ok(vec![3, 1]), run_bounded(3, peeking).run(scanner()));
+    }
+}
@@ -1,15 +1,115 @@
+pub mod bounded;
+pub mod continuation;
+pub mod history;
+pub mod iter;
+pub mod layout;
+pub mod preprocessed;
+pub mod read;
+pub mod scoped;
+pub mod slice;
+pub mod string;
+pub mod token_set;
+
+use crate::error;
+
+use std::any::Any;
+use std::marker::PhantomData;
 use std::rc::Rc;
 
 pub trait Scanner {
     type Token: Token;
+    // Must change whenever a token is consumed
+    type Position: Clone + PartialEq + Into<error::Position>;
     fn from_scanner(scanner: &Self) -> Self;
     fn scan_token(self) -> Self;
     fn is_finished(&self) -> bool;
     fn current_token(&self) -> Rc<Self::Token>;
     fn next_token(&self) -> Rc<Self::Token>;
+    fn position(&self) -> Self::Position;
+
+    // Enough of the scanner's state to rewind it with `restore`, used when
+    // backtracking. By default this is a copy of the whole scanner made with
+    // `from_scanner`; scanners that can rewind from less, such as an index,
+    // override both.
+    fn checkpoint(&self) -> Checkpoint<Self>
+    where Self: 'static + Sized {
+        Checkpoint::new(Self::from_scanner(self))
+    }
+
+    fn restore(self, checkpoint: Checkpoint<Self>) -> Self
+    where Self: 'static + Sized {
+        checkpoint.into_state()
+    }
+
+    // The token `n` places ahead, so `peek_n(1)` is `next_token()` and
+    // `peek_n(0)` is `current_token()`. Past the end of the input this is
+    // the same token `next_token` gives at the end.
+    fn peek_n(&self, n: usize) -> Rc<Self::Token>
+    where Self: Sized {
+        if n == 0 {
+            return self.current_token();
+        }
+        let mut scanner = Self::from_scanner(self);
+        for _ in 1..n {
+            if scanner.is_finished() {
+                break;
+            }
+            scanner = scanner.scan_token();
+        }
+        scanner.next_token()
+    }
+}
+
+// A snapshot of a scanner of type `S`, taken by `Scanner::checkpoint` and
+// given back to `Scanner::restore`. What state it holds is up to the scanner.
+pub struct Checkpoint<S: ?Sized> {
+    state: Box<dyn Any>,
+    scanner: PhantomData<S>
+}
+
+impl<S: ?Sized> Checkpoint<S> {
+    pub fn new<T: 'static>(state: T) -> Self {
+        Checkpoint {
+            state: Box::new(state),
+            scanner: PhantomData
+        }
+    }
+
+    // The state given to `new`. Panics if it isn't a `T`, which means the
+    // scanner restores a different kind of state than it checkpoints.
+    pub fn into_state<T: 'static>(self) -> T {
+        *self.state.downcast().expect("checkpoint holds state of the wrong type")
+    }
 }
 
 pub trait Token {
     type TokenType: PartialEq;
     fn t_type(&self) -> Self::TokenType;
-}
\ No newline at end of file
+}
+
+// Tokens that carry a payload, such as a number literal's value or an
+// identifier's name. This is a separate trait so that tokens without one
+// don't need to implement it.
+pub trait TokenValue: Token {
+    type Value;
+    fn value(&self) -> Self::Value;
+}
+
+// Tokens that carry the text they were scanned from
+pub trait Lexeme: Token {
+    fn text(&self) -> &str;
+}
+
+// Tokens such as whitespace and comments, which a grammar skips but a
+// formatter has to keep. A line break ends the trivia that trails a token.
+pub trait Trivia: Token {
+    fn is_trivia(&self) -> bool;
+    fn is_line_break(&self) -> bool;
+}
+
+// Maps each token type to a distinct index below `COUNT`, so that sets of
+// token types can be stored as bitsets
+pub trait TokenTypeIndex {
+    const COUNT: usize;
+    fn index(&self) -> usize;
+}
@@ -0,0 +1,88 @@
+use crate::scanner::{Checkpoint, Scanner};
+
+use std::rc::Rc;
+
+// Wraps a scanner so that at most `budget` tokens can be consumed. Looking at
+// the next token is always allowed; only trying to consume one past the
+// budget counts as exceeding it. The scanner then stays where it is and
+// appears finished, with `next_token` still giving the token it stopped
+// before. Since the flag is part of the checkpoint, a lookahead that went past
+// the budget and was backtracked doesn't count.
+pub struct BoundedScanner<S: Scanner> {
+    scanner: S,
+    remaining: usize,
+    exceeded: bool
+}
+
+impl<S: Scanner> BoundedScanner<S> {
+    pub fn new(scanner: S, budget: usize) -> Self {
+        BoundedScanner {
+            scanner,
+            remaining: budget,
+            exceeded: false
+        }
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    pub fn into_inner(self) -> S {
+        self.scanner
+    }
+}
+
+impl<S: Scanner> Scanner for BoundedScanner<S> {
+    type Token = S::Token;
+    type Position = S::Position;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        BoundedScanner {
+            scanner: S::from_scanner(&scanner.scanner),
+            remaining: scanner.remaining,
+            exceeded: scanner.exceeded
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self>
+    where Self: 'static {
+        Checkpoint::new((self.scanner.checkpoint(), self.remaining, self.exceeded))
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self
+    where Self: 'static {
+        let (checkpoint, remaining, exceeded): (Checkpoint<S>, usize, bool) = checkpoint.into_state();
+        self.scanner = self.scanner.restore(checkpoint);
+        self.remaining = remaining;
+        self.exceeded = exceeded;
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        if self.remaining == 0 {
+            if !self.scanner.is_finished() {
+                self.exceeded = true;
+            }
+        } else {
+            self.remaining -= 1;
+            self.scanner = self.scanner.scan_token();
+        }
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.exceeded || self.scanner.is_finished()
+    }
+
+    fn current_token(&self) -> Rc<Self::Token> {
+        self.scanner.current_token()
+    }
+
+    fn next_token(&self) -> Rc<Self::Token> {
+        self.scanner.next_token()
+    }
+
+    fn position(&self) -> Self::Position {
+        self.scanner.position()
+    }
+}
@@ -0,0 +1,153 @@
+use crate::scanner::{Checkpoint, Scanner};
+use crate::scanner::string::{CharToken, TabStops};
+
+use std::rc::Rc;
+
+// Scans the characters of another scanner, joining lines that end in a
+// backslash, so `"a \\\n b"` reads as `"a  b"`. A `\r\n` after the backslash
+// is joined the same way. A backslash that isn't right before a line break,
+// including one at the very end of the input, is an ordinary character.
+// Characters keep the positions they have in the source.
+pub struct ContinuationScanner<S: Scanner<Token = CharToken>> {
+    inner: S,
+    current: Rc<CharToken>
+}
+
+impl<S: Scanner<Token = CharToken>> ContinuationScanner<S> {
+    pub fn new(inner: S) -> Self {
+        ContinuationScanner {
+            current: inner.current_token(),
+            inner: skip_continuations(inner)
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+fn skip_continuations<S: Scanner<Token = CharToken>>(mut inner: S) -> S {
+    loop {
+        let length = match (inner.peek_n(1).ch, inner.peek_n(2).ch, inner.peek_n(3).ch) {
+            ('\\', '\n', _) => 2,
+            ('\\', '\r', '\n') => 3,
+            _ => return inner
+        };
+        for _ in 0..length {
+            inner = inner.scan_token();
+        }
+    }
+}
+
+impl<S: TabStops> TabStops for ContinuationScanner<S> {
+    fn set_tab_width(mut self, tab_width: usize) -> Self {
+        self.inner = self.inner.set_tab_width(tab_width);
+        self
+    }
+}
+
+impl<S: Scanner<Token = CharToken>> Scanner for ContinuationScanner<S> {
+    type Token = CharToken;
+    type Position = S::Position;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        ContinuationScanner {
+            inner: S::from_scanner(&scanner.inner),
+            current: Rc::clone(&scanner.current)
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self>
+    where Self: 'static {
+        Checkpoint::new((self.inner.checkpoint(), Rc::clone(&self.current)))
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self
+    where Self: 'static {
+        let (checkpoint, current): (Checkpoint<S>, Rc<CharToken>) = checkpoint.into_state();
+        self.inner = self.inner.restore(checkpoint);
+        self.current = current;
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        self.inner = self.inner.scan_token();
+        self.current = self.inner.current_token();
+        self.inner = skip_continuations(self.inner);
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+
+    fn current_token(&self) -> Rc<CharToken> {
+        Rc::clone(&self.current)
+    }
+
+    fn next_token(&self) -> Rc<CharToken> {
+        self.inner.next_token()
+    }
+
+    fn position(&self) -> S::Position {
+        self.inner.position()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Position;
+    use crate::scanner::layout::{Layout, LayoutScanner};
+    use crate::scanner::string::StringScanner;
+
+    fn text(source: &str) -> String {
+        let mut scanner = ContinuationScanner::new(StringScanner::new(source));
+        let mut text = String::new();
+        while !scanner.is_finished() {
+            scanner = scanner.scan_token();
+            text.push(scanner.current_token().ch);
+        }
+        text
+    }
+
+    #[test]
+    fn scan_test() {
+        assert_eq!("a +  b\nc", text("a + \\\n b\nc"));
+        assert_eq!("ab", text("a\\\r\n\\\nb"));
+        assert_eq!("\\", text("\\\n\\"));
+
+        // A backslash anywhere else is kept
+        assert_eq!("a\\b a\\", text("a\\b a\\"));
+
+        // Characters keep their place in the source
+        let scanner = ContinuationScanner::new(StringScanner::new("a\\\nb")).scan_token();
+        let checkpoint = scanner.checkpoint();
+        let scanner = scanner.scan_token();
+        assert_eq!(Position::new(3, 2, 1), scanner.current_token().position);
+        assert!(scanner.is_finished());
+
+        let scanner = scanner.restore(checkpoint);
+        assert_eq!('a', scanner.current_token().ch);
+        assert_eq!('b', scanner.next_token().ch);
+    }
+
+    #[test]
+    fn layout_test() {
+        // The continued line belongs to the line before it, so its
+        // indentation doesn't open a block
+        let source = "a = 1 +\\\n    2\nb";
+        let mut scanner = LayoutScanner::new(ContinuationScanner::new(StringScanner::new(source)));
+        let mut layouts = vec![];
+        while !scanner.is_finished() {
+            scanner = scanner.scan_token();
+            layouts.push(scanner.current_token().layout);
+        }
+        assert!(!layouts.contains(&Layout::Indent));
+        let text: String = layouts.iter().filter_map(|layout| match layout {
+            Layout::Char(ch) => Some(*ch),
+            _ => None
+        }).collect();
+        assert_eq!("a = 1 +    2\nb", text);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::scanner::{Checkpoint, Scanner};
+
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+// Scanners that remember the tokens they most recently consumed
+pub trait TokenHistory: Scanner {
+    // Up to `k` of the most recently consumed tokens, oldest first
+    fn recent_tokens(&self, k: usize) -> Vec<Rc<Self::Token>>;
+}
+
+// Wraps a scanner, keeping the last `capacity` consumed tokens
+pub struct HistoryScanner<S: Scanner> {
+    scanner: S,
+    capacity: usize,
+    history: VecDeque<Rc<S::Token>>
+}
+
+impl<S: Scanner> HistoryScanner<S> {
+    pub fn new(scanner: S, capacity: usize) -> Self {
+        HistoryScanner {
+            scanner,
+            capacity,
+            history: VecDeque::with_capacity(capacity)
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.scanner
+    }
+}
+
+impl<S: Scanner> Scanner for HistoryScanner<S> {
+    type Token = S::Token;
+    type Position = S::Position;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        HistoryScanner {
+            scanner: S::from_scanner(&scanner.scanner),
+            capacity: scanner.capacity,
+            history: scanner.history.clone()
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self>
+    where Self: 'static {
+        Checkpoint::new((self.scanner.checkpoint(), self.history.clone()))
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self
+    where Self: 'static {
+        let (checkpoint, history): (Checkpoint<S>, VecDeque<Rc<S::Token>>) = checkpoint.into_state();
+        self.scanner = self.scanner.restore(checkpoint);
+        self.history = history;
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        if !self.scanner.is_finished() {
+            self.scanner = self.scanner.scan_token();
+            if self.capacity > 0 {
+                if self.history.len() == self.capacity {
+                    self.history.pop_front();
+                }
+                self.history.push_back(self.scanner.current_token());
+            }
+        } else {
+            self.scanner = self.scanner.scan_token();
+        }
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.scanner.is_finished()
+    }
+
+    fn current_token(&self) -> Rc<Self::Token> {
+        self.scanner.current_token()
+    }
+
+    fn next_token(&self) -> Rc<Self::Token> {
+        self.scanner.next_token()
+    }
+
+    fn position(&self) -> Self::Position {
+        self.scanner.position()
+    }
+}
+
+impl<S: Scanner> TokenHistory for HistoryScanner<S> {
+    fn recent_tokens(&self, k: usize) -> Vec<Rc<Self::Token>> {
+        let skip = self.history.len().saturating_sub(k);
+        self.history.iter().skip(skip).cloned().collect()
+    }
+}
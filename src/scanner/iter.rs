@@ -0,0 +1,154 @@
+use crate::scanner::{Checkpoint, Scanner, Token};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Tokens pulled from the iterator so far, shared by every copy of a scanner
+struct Buffer<I: Iterator> {
+    iter: I,
+    tokens: Vec<Rc<I::Item>>
+}
+
+impl<I: Iterator> Buffer<I> {
+    // Pulls tokens until `index` is buffered or the iterator runs out
+    fn get(&mut self, index: usize) -> Option<Rc<I::Item>> {
+        while self.tokens.len() <= index {
+            match self.iter.next() {
+                Some(token) => self.tokens.push(Rc::new(token)),
+                None => return None
+            }
+        }
+        Some(Rc::clone(&self.tokens[index]))
+    }
+}
+
+// Scans over the tokens of an iterator, such as a lexer, pulling them only as
+// they are needed. Since an iterator can't be rewound, every token pulled is
+// kept in a buffer shared by all copies of the scanner, so backtracking is
+// just a matter of moving an index. The buffer lives as long as any copy, so
+// the memory used grows with the whole input rather than with the lookahead.
+// `end` is reported like in `SliceScanner`.
+pub struct IterScanner<I: Iterator> {
+    buffer: Rc<RefCell<Buffer<I>>>,
+    end: Rc<I::Item>,
+    index: usize
+}
+
+impl<I: Iterator> IterScanner<I>
+where I::Item: Token {
+    pub fn new(iter: I, end: I::Item) -> Self {
+        IterScanner {
+            buffer: Rc::new(RefCell::new(Buffer { iter, tokens: vec![] })),
+            end: Rc::new(end),
+            index: 0
+        }
+    }
+
+    fn token_at(&self, index: usize) -> Rc<I::Item> {
+        self.buffer.borrow_mut().get(index).unwrap_or_else(|| Rc::clone(&self.end))
+    }
+}
+
+impl<I: Iterator> Scanner for IterScanner<I>
+where I::Item: Token {
+    type Token = I::Item;
+    type Position = usize;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        IterScanner {
+            buffer: Rc::clone(&scanner.buffer),
+            end: Rc::clone(&scanner.end),
+            index: scanner.index
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self> {
+        Checkpoint::new(self.index)
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self {
+        self.index = checkpoint.into_state();
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        if !self.is_finished() {
+            self.index += 1;
+        }
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.buffer.borrow_mut().get(self.index).is_none()
+    }
+
+    fn current_token(&self) -> Rc<I::Item> {
+        match self.index {
+            0 => Rc::clone(&self.end),
+            index => self.token_at(index - 1)
+        }
+    }
+
+    fn next_token(&self) -> Rc<I::Item> {
+        self.token_at(self.index)
+    }
+
+    fn position(&self) -> usize {
+        self.index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::parser::basic_functions::{advance, is_at_end, matches};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Letter(char);
+
+    impl Token for Letter {
+        type TokenType = char;
+        fn t_type(&self) -> char {
+            self.0
+        }
+    }
+
+    fn scanner(source: &'static str) -> IterScanner<impl Iterator<Item = Letter>> {
+        IterScanner::new(source.chars().map(Letter), Letter('\0'))
+    }
+
+    #[test]
+    fn scan_test() {
+        let scanner = scanner("abc");
+        assert_eq!(Letter('\0'), *scanner.current_token());
+        assert_eq!(Letter('a'), *scanner.next_token());
+
+        let scanner = scanner.scan_token().scan_token();
+        assert_eq!(Letter('b'), *scanner.current_token());
+        assert_eq!(Letter('c'), *scanner.next_token());
+
+        let scanner = scanner.scan_token();
+        assert!(scanner.is_finished());
+        assert_eq!(Letter('\0'), *scanner.next_token());
+        assert_eq!(3, scanner.scan_token().position());
+    }
+
+    #[test]
+    fn parse_test() {
+        let parser = matches('a') >> |a|
+                     matches('x').or_else(Parser::result(false)) >> move |x|
+                     advance() >> move |b: Rc<Letter>|
+                     is_at_end() >> move |at_end|
+                     Parser::result((a, x, b.0, at_end));
+        assert_eq!(Ok((true, false, 'b', false)), parser.run(scanner("abc")));
+
+        // Tokens already pulled from the iterator can be gone back over
+        let behind = scanner("abc").scan_token();
+        let checkpoint = behind.checkpoint();
+        let ahead = behind.scan_token().scan_token();
+        assert_eq!(Letter('c'), *ahead.current_token());
+        let behind = ahead.restore(checkpoint);
+        assert_eq!(Letter('a'), *behind.current_token());
+    }
+}
@@ -0,0 +1,514 @@
+use crate::error::{ParseError, Position};
+use crate::scanner::{Checkpoint, Scanner, Token};
+use crate::scanner::string::{CharToken, TabStops};
+
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Char(char),
+    // The start of a line indented further than the one before it
+    Indent,
+    // The start of a line indented less than the one before it; one for each
+    // level closed, including the levels still open at the end of the input
+    Dedent,
+    // A line whose indentation doesn't match any of the enclosing levels.
+    // It's then treated as a new level, so parsing can carry on.
+    Misaligned
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutToken {
+    pub layout: Layout,
+    pub position: Position,
+    // The indentation of the line the token is on
+    pub indent: usize,
+    // Set on `Misaligned` tokens
+    pub misalignment: Option<Misalignment>
+}
+
+// What a misaligned line was out of line with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Misalignment {
+    // The indentation of the line
+    pub found: usize,
+    // The open indentation levels, from the outermost. Lining up with one of
+    // them would have continued its block.
+    pub levels: Vec<usize>,
+    // Where the line that opened the innermost block the line is in starts,
+    // unless that block is the top level
+    pub opener: Option<Position>
+}
+
+impl LayoutToken {
+    // The error to report for a `Misaligned` token, such as "expected an
+    // indentation of 4 to continue the block started at line 2, column 1,
+    // found 6"
+    pub fn indentation_error(&self) -> Option<ParseError> {
+        let misalignment = self.misalignment.as_ref()?;
+        let expected = misalignment.levels.iter().rev()
+            .find(|&&level| level < misalignment.found)
+            .copied()
+            .unwrap_or(0);
+        let message = match misalignment.opener {
+            Some(opener) => format!("expected an indentation of {} to continue the block started at {}, found {}",
+                                    expected, opener, misalignment.found),
+            None => format!("expected an indentation of {}, found {}", expected, misalignment.found)
+        };
+        Some(ParseError::new(message).at(self.position))
+    }
+}
+
+impl Token for LayoutToken {
+    type TokenType = Layout;
+    fn t_type(&self) -> Layout {
+        self.layout
+    }
+}
+
+// Where a `LayoutScanner` is: the position in the source, along with how many
+// tokens have been consumed, since an `Indent` or `Dedent` takes up no source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutPosition {
+    pub position: Position,
+    pub tokens: usize
+}
+
+impl From<LayoutPosition> for Position {
+    fn from(position: LayoutPosition) -> Self {
+        position.position
+    }
+}
+
+// Scans the characters of another scanner for an indentation-sensitive
+// language, as in Python. The leading whitespace of each line is replaced by
+// `Indent`, `Dedent` or nothing, depending on how it compares to a stack of
+// indentation levels, and every open level is closed with a `Dedent` at the
+// end of the input. Lines that are only whitespace don't affect the levels.
+// Spaces count as one column and tabs as reaching the next multiple of the
+// tab width, which is 8 unless set with `with_tab_width`. The inner scanner is
+// given the same tab width, so the columns of positions agree with the
+// indentation. Indentation that mixes tabs and spaces only lines up at some
+// tab widths, so each line that does is reported in `warnings`.
+pub struct LayoutScanner<S: Scanner<Token = CharToken>> {
+    inner: S,
+    levels: Vec<Level>,
+    // Where the last line that wasn't blank starts
+    last_line: Option<Position>,
+    // The indentation of the line being read
+    indent: usize,
+    warnings: Vec<ParseError>,
+    // The tokens read from `inner` but not yet consumed
+    queue: VecDeque<Rc<LayoutToken>>,
+    at_line_start: bool,
+    current: Rc<LayoutToken>,
+    tokens: usize,
+    tab_width: usize
+}
+
+// An open indentation level, along with where the line that opened it starts
+#[derive(Clone)]
+struct Level {
+    indent: usize,
+    opener: Option<Position>
+}
+
+struct LayoutCheckpoint<S: Scanner> {
+    inner: Checkpoint<S>,
+    levels: Vec<Level>,
+    last_line: Option<Position>,
+    indent: usize,
+    warnings: usize,
+    queue: VecDeque<Rc<LayoutToken>>,
+    at_line_start: bool,
+    current: Rc<LayoutToken>,
+    tokens: usize
+}
+
+impl<S: TabStops> LayoutScanner<S> {
+    pub fn new(inner: S) -> Self {
+        LayoutScanner::with_tab_width(inner, 8)
+    }
+
+    pub fn with_tab_width(inner: S, tab_width: usize) -> Self {
+        let tab_width = tab_width.max(1);
+        let inner = inner.set_tab_width(tab_width);
+        let start = inner.next_token().position;
+        LayoutScanner {
+            inner,
+            levels: vec![Level { indent: 0, opener: None }],
+            last_line: None,
+            indent: 0,
+            warnings: vec![],
+            queue: VecDeque::new(),
+            at_line_start: true,
+            current: Rc::new(LayoutToken { layout: Layout::Char('\0'), position: start, indent: 0, misalignment: None }),
+            tokens: 0,
+            tab_width
+        }.fill()
+    }
+}
+
+impl<S: Scanner<Token = CharToken>> LayoutScanner<S> {
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    // The warnings for the lines read so far
+    pub fn warnings(&self) -> &[ParseError] {
+        &self.warnings
+    }
+
+    // Reads from `inner` until there's a token to consume, unless the input
+    // has run out
+    fn fill(mut self) -> Self {
+        while self.queue.is_empty() {
+            if self.at_line_start {
+                self = self.start_line();
+            } else if self.inner.is_finished() {
+                return self;
+            } else {
+                self.inner = self.inner.scan_token();
+                let token = self.inner.current_token();
+                self.at_line_start = token.ch == '\n';
+                self.push(Layout::Char(token.ch), token.position);
+            }
+        }
+        self
+    }
+
+    // Measures the indentation of the line about to be read and queues the
+    // tokens it calls for
+    fn start_line(mut self) -> Self {
+        let line_start = self.inner.next_token().position;
+        let mut width = 0;
+        let (mut spaces, mut tabs) = (false, false);
+        while !self.inner.is_finished() {
+            match self.inner.next_token().ch {
+                ' ' => {
+                    width += 1;
+                    spaces = true;
+                }
+                '\t' => {
+                    width += self.tab_width - width % self.tab_width;
+                    tabs = true;
+                }
+                _ => break
+            }
+            self.inner = self.inner.scan_token();
+        }
+        self.indent = width;
+
+        let next = self.inner.next_token();
+        if self.inner.is_finished() {
+            while self.levels.len() > 1 {
+                self.levels.pop();
+                self.push(Layout::Dedent, next.position);
+            }
+            self.at_line_start = false;
+            return self;
+        }
+        if next.ch == '\n' || next.ch == '\r' {
+            // Blank lines are left to the grammar
+            self.at_line_start = false;
+            return self;
+        }
+
+        if spaces && tabs {
+            self.warnings.push(ParseError::warning("indentation mixes tabs and spaces").at(line_start));
+        }
+        let top = self.top();
+        if width > top {
+            self.levels.push(Level { indent: width, opener: self.last_line });
+            self.push(Layout::Indent, next.position);
+        } else if width < top {
+            let levels = self.levels.iter().map(|level| level.indent).collect();
+            while self.top() > width {
+                self.levels.pop();
+                self.push(Layout::Dedent, next.position);
+            }
+            if self.top() != width {
+                let opener = self.levels.last().unwrap().opener;
+                self.levels.push(Level { indent: width, opener: self.last_line });
+                let misalignment = Misalignment { found: width, levels, opener };
+                self.queue.push_back(Rc::new(LayoutToken {
+                    layout: Layout::Misaligned,
+                    position: next.position,
+                    indent: width,
+                    misalignment: Some(misalignment)
+                }));
+            }
+        }
+        self.last_line = Some(next.position);
+        self.at_line_start = false;
+        self
+    }
+
+    fn top(&self) -> usize {
+        self.levels.last().unwrap().indent
+    }
+
+    fn push(&mut self, layout: Layout, position: Position) {
+        self.queue.push_back(Rc::new(LayoutToken { layout, position, indent: self.indent, misalignment: None }));
+    }
+}
+
+impl<S: Scanner<Token = CharToken>> Scanner for LayoutScanner<S> {
+    type Token = LayoutToken;
+    type Position = LayoutPosition;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        LayoutScanner {
+            inner: S::from_scanner(&scanner.inner),
+            levels: scanner.levels.clone(),
+            last_line: scanner.last_line,
+            indent: scanner.indent,
+            warnings: scanner.warnings.clone(),
+            queue: scanner.queue.clone(),
+            at_line_start: scanner.at_line_start,
+            current: Rc::clone(&scanner.current),
+            tokens: scanner.tokens,
+            tab_width: scanner.tab_width
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self>
+    where Self: 'static {
+        Checkpoint::new(LayoutCheckpoint {
+            inner: self.inner.checkpoint(),
+            levels: self.levels.clone(),
+            last_line: self.last_line,
+            indent: self.indent,
+            warnings: self.warnings.len(),
+            queue: self.queue.clone(),
+            at_line_start: self.at_line_start,
+            current: Rc::clone(&self.current),
+            tokens: self.tokens
+        })
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self
+    where Self: 'static {
+        let checkpoint: LayoutCheckpoint<S> = checkpoint.into_state();
+        self.inner = self.inner.restore(checkpoint.inner);
+        self.levels = checkpoint.levels;
+        self.last_line = checkpoint.last_line;
+        self.indent = checkpoint.indent;
+        self.warnings.truncate(checkpoint.warnings);
+        self.queue = checkpoint.queue;
+        self.at_line_start = checkpoint.at_line_start;
+        self.current = checkpoint.current;
+        self.tokens = checkpoint.tokens;
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        match self.queue.pop_front() {
+            Some(token) => {
+                self.current = token;
+                self.tokens += 1;
+                self.fill()
+            }
+            None => {
+                self.current = self.next_token();
+                self
+            }
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    fn current_token(&self) -> Rc<LayoutToken> {
+        Rc::clone(&self.current)
+    }
+
+    fn next_token(&self) -> Rc<LayoutToken> {
+        match self.queue.front() {
+            Some(token) => Rc::clone(token),
+            None => Rc::new(LayoutToken {
+                layout: Layout::Char('\0'),
+                position: self.inner.next_token().position,
+                indent: self.indent,
+                misalignment: None
+            })
+        }
+    }
+
+    fn position(&self) -> LayoutPosition {
+        LayoutPosition { position: self.next_token().position, tokens: self.tokens }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::parser::basic_functions::{consume, matches};
+    use crate::parser::combinators::many;
+    use crate::scanner::string::StringScanner;
+
+    // The layout of every token, with the characters of each line collected
+    // into a string
+    fn layout(scanner: LayoutScanner<StringScanner>) -> Vec<String> {
+        let mut scanner = scanner;
+        let mut items = vec![];
+        let mut line = String::new();
+        while !scanner.is_finished() {
+            scanner = scanner.scan_token();
+            match scanner.current_token().layout {
+                Layout::Char('\n') => items.push(std::mem::take(&mut line)),
+                Layout::Char(ch) => line.push(ch),
+                other => {
+                    if !line.is_empty() {
+                        items.push(std::mem::take(&mut line));
+                    }
+                    items.push(format!("{:?}", other));
+                }
+            }
+        }
+        if !line.is_empty() {
+            items.push(line);
+        }
+        items
+    }
+
+    #[test]
+    fn scan_test() {
+        let source = "if a:\n    b\n    if c:\n        d\ne";
+        assert_eq!(
+            vec!["if a:", "Indent", "b", "if c:", "Indent", "d", "Dedent", "Dedent", "e"],
+            layout(LayoutScanner::new(StringScanner::new(source)))
+        );
+
+        // Blank lines leave the levels alone, and open levels are closed at the end
+        let source = "a\n  b\n\n   \n  c\n";
+        assert_eq!(
+            vec!["a", "Indent", "b", "", "", "c", "Dedent"],
+            layout(LayoutScanner::new(StringScanner::new(source)))
+        );
+
+        // A tab reaches the next multiple of the tab width
+        let source = "a\n\tb\n    c\n  d";
+        assert_eq!(
+            vec!["a", "Indent", "b", "c", "Dedent", "Misaligned", "d"],
+            layout(LayoutScanner::with_tab_width(StringScanner::new(source), 4))
+        );
+        assert_eq!(
+            vec!["a", "Indent", "b", "Dedent", "Misaligned", "c", "Dedent", "Misaligned", "d"],
+            layout(LayoutScanner::new(StringScanner::new(source)))
+        );
+    }
+
+    #[test]
+    fn tab_width_test() {
+        // The indentation of each line's first character
+        fn indents(scanner: LayoutScanner<StringScanner>) -> Vec<(char, usize)> {
+            let mut scanner = scanner;
+            let mut indents = vec![];
+            while !scanner.is_finished() {
+                scanner = scanner.scan_token();
+                let token = scanner.current_token();
+                if let Layout::Char(ch) = token.layout {
+                    if ch.is_alphabetic() {
+                        indents.push((ch, token.indent));
+                    }
+                }
+            }
+            indents
+        }
+
+        // A tab rounds up to the next tab stop, even after spaces
+        let source = "a\n\tb\n \tc\n  \t d";
+        assert_eq!(vec![('a', 0), ('b', 4), ('c', 4), ('d', 5)],
+                   indents(LayoutScanner::with_tab_width(StringScanner::new(source), 4)));
+        assert_eq!(vec![('a', 0), ('b', 8), ('c', 8), ('d', 9)],
+                   indents(LayoutScanner::new(StringScanner::new(source))));
+        assert_eq!(vec![('a', 0), ('b', 1), ('c', 2), ('d', 4)],
+                   indents(LayoutScanner::with_tab_width(StringScanner::new(source), 1)));
+
+        // Diagnostics are at columns that count tabs the same way
+        let source = "a:\n         b\n\tc";
+        for &tab_width in &[1, 4, 8] {
+            let mut scanner = LayoutScanner::with_tab_width(StringScanner::new(source), tab_width);
+            while scanner.next_token().layout != Layout::Misaligned {
+                scanner = scanner.scan_token();
+            }
+            let message = format!("expected an indentation of 0, found {}", tab_width);
+            assert_eq!(Some(ParseError::new(message).at(Position::new(15, 3, tab_width + 1))),
+                       scanner.next_token().indentation_error());
+        }
+    }
+
+    #[test]
+    fn mixed_indentation_test() {
+        let source = "a\n\tb\n \tc\n  \t d";
+        let scanner = LayoutScanner::with_tab_width(StringScanner::new(source), 4);
+        let checkpoint = scanner.checkpoint();
+        let mut scanner = scanner;
+        while !scanner.is_finished() {
+            scanner = scanner.scan_token();
+        }
+        assert_eq!(&[
+            ParseError::warning("indentation mixes tabs and spaces").at(Position::new(5, 3, 1)),
+            ParseError::warning("indentation mixes tabs and spaces").at(Position::new(9, 4, 1))
+        ], scanner.warnings());
+
+        // Backtracking forgets the warnings for lines that will be read again
+        assert!(scanner.restore(checkpoint).warnings().is_empty());
+    }
+
+    #[test]
+    fn misaligned_test() {
+        let source = "a:\n  b:\n      c\n    d";
+        let mut scanner = LayoutScanner::new(StringScanner::new(source));
+        while scanner.next_token().layout != Layout::Misaligned {
+            scanner = scanner.scan_token();
+        }
+        let token = scanner.next_token();
+        assert_eq!(Some(Misalignment { found: 4, levels: vec![0, 2, 6], opener: Some(Position::new(0, 1, 1)) }),
+                   token.misalignment);
+        assert_eq!(Some(ParseError::new("expected an indentation of 2 to continue the block started at line 1, column 1, found 4")
+                        .at(Position::new(20, 4, 5))),
+                   token.indentation_error());
+
+        // Within the top level there is no opener to name
+        let source = "a\n  b\n c";
+        let mut scanner = LayoutScanner::new(StringScanner::new(source));
+        while scanner.next_token().layout != Layout::Misaligned {
+            scanner = scanner.scan_token();
+        }
+        assert_eq!(Some(ParseError::new("expected an indentation of 0, found 1").at(Position::new(7, 3, 2))),
+                   scanner.next_token().indentation_error());
+        assert_eq!(None, scanner.scan_token().next_token().indentation_error());
+    }
+
+    #[test]
+    fn parse_test() {
+        // block = "x" "\n" (Indent block+ Dedent)?, returning how many lines
+        // it spans
+        fn block() -> Parser<LayoutScanner<StringScanner>, usize> {
+            consume(Layout::Char('x'), "expected x".to_string()) >> |_|
+            matches(Layout::Char('\n')) >> |_|
+            matches(Layout::Indent).if_else(
+                many(block) >> |blocks|
+                consume(Layout::Dedent, "expected dedent".to_string()) >> move |_|
+                Parser::result(1 + blocks.iter().sum::<usize>()),
+                Parser::result(1)
+            )
+        }
+
+        let scanner = || LayoutScanner::new(StringScanner::new("x\n  x\n    x\n  x\n"));
+        assert_eq!(Ok(4), block().run(scanner()));
+
+        // Backtracking past an indent restores the indentation levels
+        let parser = (consume(Layout::Char('x'), "expected x".to_string()) >> |_|
+                      matches(Layout::Char('\n')) >> |_|
+                      consume(Layout::Dedent, "expected dedent".to_string()) >> |_|
+                      Parser::result(0))
+                     .or_else(block());
+        assert_eq!(Ok(4), parser.run(scanner()));
+    }
+}
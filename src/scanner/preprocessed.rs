@@ -0,0 +1,170 @@
+use crate::error;
+use crate::scanner::{Checkpoint, Scanner, Token};
+use crate::scanner::slice::SliceScanner;
+
+use std::rc::Rc;
+
+// Where a `PreprocessedScanner` is: the index into the rewritten tokens, along
+// with the position in the original tokens that the next one stands for.
+// Several rewritten tokens can stand for the same original one, as when a
+// macro call is expanded, so it's `index` that changes with every token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MappedPosition {
+    pub index: usize,
+    pub original: usize
+}
+
+impl From<MappedPosition> for error::Position {
+    fn from(position: MappedPosition) -> Self {
+        position.original.into()
+    }
+}
+
+// Scans tokens that were rewritten before parsing, as by conditional
+// compilation or macro expansion, while reporting positions in the tokens
+// they were rewritten from. `transform` is given every token paired with its
+// position and returns the new tokens, each paired with the position of the
+// original token it stands for.
+pub struct PreprocessedScanner<T: Token + Clone> {
+    tokens: SliceScanner<T>,
+    // The original position of each rewritten token
+    origins: Rc<[usize]>,
+    // Where the original tokens end
+    end: usize
+}
+
+impl<T: Token + Clone> PreprocessedScanner<T> {
+    pub fn new<F>(tokens: Vec<T>, end: T, transform: F) -> Self
+    where F: FnOnce(Vec<(usize, T)>) -> Vec<(usize, T)> {
+        PreprocessedScanner::starting_at(0, tokens, end, transform)
+    }
+
+    // Like `new`, for tokens whose first one is at position `start`
+    pub(crate) fn starting_at<F>(start: usize, tokens: Vec<T>, end: T, transform: F) -> Self
+    where F: FnOnce(Vec<(usize, T)>) -> Vec<(usize, T)> {
+        let original_end = start + tokens.len();
+        let numbered = tokens.into_iter().enumerate().map(|(i, token)| (start + i, token)).collect();
+        let (origins, tokens): (Vec<_>, Vec<_>) = transform(numbered).into_iter().unzip();
+        PreprocessedScanner {
+            tokens: SliceScanner::new(tokens, end),
+            origins: Rc::from(origins),
+            end: original_end
+        }
+    }
+
+    // The position in the original tokens of the next token, or of the end of
+    // the original tokens once the rewritten ones run out
+    pub fn original_position(&self) -> usize {
+        self.origins.get(self.tokens.position()).copied().unwrap_or(self.end)
+    }
+}
+
+impl<T: Token + Clone> Scanner for PreprocessedScanner<T> {
+    type Token = T;
+    type Position = MappedPosition;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        PreprocessedScanner {
+            tokens: SliceScanner::from_scanner(&scanner.tokens),
+            origins: Rc::clone(&scanner.origins),
+            end: scanner.end
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self>
+    where Self: 'static {
+        Checkpoint::new(self.tokens.checkpoint())
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self
+    where Self: 'static {
+        self.tokens = self.tokens.restore(checkpoint.into_state());
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        self.tokens = self.tokens.scan_token();
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.tokens.is_finished()
+    }
+
+    fn current_token(&self) -> Rc<T> {
+        self.tokens.current_token()
+    }
+
+    fn next_token(&self) -> Rc<T> {
+        self.tokens.next_token()
+    }
+
+    fn position(&self) -> MappedPosition {
+        MappedPosition { index: self.tokens.position(), original: self.original_position() }
+    }
+
+    fn peek_n(&self, n: usize) -> Rc<T> {
+        self.tokens.peek_n(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ParseError, ParseErrors};
+    use crate::parser::Parser;
+    use crate::parser::basic_functions::{advance, consume};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum TestToken {
+        Ident,
+        Semicolon,
+        Comment,
+        // Expands to two identifiers
+        Macro,
+        End
+    }
+
+    impl Token for TestToken {
+        type TokenType = TestToken;
+        fn t_type(&self) -> TestToken {
+            *self
+        }
+    }
+
+    fn expand(tokens: Vec<(usize, TestToken)>) -> Vec<(usize, TestToken)> {
+        tokens.into_iter().flat_map(|(position, token)| match token {
+            TestToken::Comment => vec![],
+            TestToken::Macro => vec![(position, TestToken::Ident); 2],
+            token => vec![(position, token)]
+        }).collect()
+    }
+
+    fn ident() -> Parser<PreprocessedScanner<TestToken>, Rc<TestToken>> {
+        consume(TestToken::Ident, "expected identifier".to_string())
+    }
+
+    #[test]
+    fn position_test() {
+        let scanner = PreprocessedScanner::new(vec![TestToken::Comment, TestToken::Macro, TestToken::Ident], TestToken::End, expand);
+        assert_eq!(MappedPosition { index: 0, original: 1 }, scanner.position());
+        let scanner = scanner.scan_token();
+        assert_eq!(MappedPosition { index: 1, original: 1 }, scanner.position());
+        let scanner = scanner.scan_token().scan_token();
+        assert_eq!(MappedPosition { index: 3, original: 3 }, scanner.position());
+        assert!(scanner.is_finished());
+    }
+
+    #[test]
+    fn parse_test() {
+        let scanner = || PreprocessedScanner::new(vec![
+            TestToken::Ident, TestToken::Comment, TestToken::Comment, TestToken::Semicolon, TestToken::Ident
+        ], TestToken::End, expand);
+
+        // The error is at the rewritten token 1, which is the original token 3
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected identifier").at(3)])),
+                   (ident() >> |_| ident()).run(scanner()));
+        assert_eq!(Ok(TestToken::Ident),
+                   (ident() >> |_| advance() >> |_| ident()).map(|token| *token).run(scanner()));
+    }
+}
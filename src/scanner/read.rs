@@ -0,0 +1,310 @@
+use crate::error::{ParseError, Position};
+use crate::scanner::{Checkpoint, Scanner};
+use crate::scanner::string::{next_tab_stop, CharToken, TabStops};
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::rc::Rc;
+
+const CHUNK_SIZE: usize = 1024;
+
+// The characters decoded so far, shared by every copy of a scanner. Only the
+// `window` characters before the furthest one asked for are kept, along with
+// any decoded after it.
+struct Source<R: Read> {
+    reader: R,
+    // Bytes read but not yet decoded, such as the start of a character split
+    // across two reads
+    pending: Vec<u8>,
+    chars: VecDeque<Rc<CharToken>>,
+    // The index of the first character in `chars`
+    start: usize,
+    window: usize,
+    // The position after the last character decoded
+    end: Position,
+    tab_width: usize,
+    finished: bool,
+    error: Option<io::Error>,
+    // Set when a character that is no longer kept is asked for
+    rewind_error: Option<ParseError>
+}
+
+impl<R: Read> Source<R> {
+    // Decodes characters until `index` is available or the input runs out,
+    // then drops those more than `window` before it. A character that was
+    // already dropped is reported as the end of the input, and the first time
+    // that happens `rewind_error` is set.
+    fn get(&mut self, index: usize) -> Option<Rc<CharToken>> {
+        if index < self.start {
+            if self.rewind_error.is_none() {
+                let kept = self.chars.front().map_or(self.end, |token| token.position);
+                let message = format!("cannot go back more than {} characters", self.window);
+                self.rewind_error = Some(ParseError::new(message).at(kept));
+            }
+            return None;
+        }
+        while self.start + self.chars.len() <= index && !(self.finished && self.pending.is_empty()) {
+            self.decode();
+        }
+        // The character before `index` is the current token of a scanner
+        // that is `window` characters back, so it is kept too
+        while self.start + self.window + 1 < index && !self.chars.is_empty() {
+            self.chars.pop_front();
+            self.start += 1;
+        }
+        self.chars.get(index - self.start).cloned()
+    }
+
+    // Decodes at least one character from `pending`, reading more bytes if
+    // needed, unless the input runs out. Invalid UTF-8 is replaced with
+    // U+FFFD.
+    fn decode(&mut self) {
+        loop {
+            let (decoded, consumed) = match std::str::from_utf8(&self.pending) {
+                Ok(text) => (text.to_string(), self.pending.len()),
+                Err(error) => {
+                    let valid = error.valid_up_to();
+                    let text = std::str::from_utf8(&self.pending[..valid]).unwrap().to_string();
+                    match error.error_len() {
+                        Some(len) if valid == 0 => ('\u{FFFD}'.to_string(), len),
+                        // Incomplete at the end of the input
+                        None if valid == 0 && self.finished => ('\u{FFFD}'.to_string(), self.pending.len()),
+                        _ => (text, valid)
+                    }
+                }
+            };
+            self.pending.drain(..consumed);
+            for ch in decoded.chars() {
+                self.push(ch);
+            }
+            if consumed > 0 || self.finished {
+                return;
+            }
+            self.read();
+        }
+    }
+
+    fn read(&mut self) {
+        let mut chunk = [0; CHUNK_SIZE];
+        match self.reader.read(&mut chunk) {
+            Ok(0) => self.finished = true,
+            Ok(n) => self.pending.extend_from_slice(&chunk[..n]),
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => {
+                self.error = Some(error);
+                self.finished = true;
+            }
+        }
+    }
+
+    fn push(&mut self, ch: char) {
+        self.chars.push_back(Rc::new(CharToken { ch, position: self.end }));
+        self.end.offset += ch.len_utf8();
+        if ch == '\n' {
+            self.end.line += 1;
+            self.end.column = 1;
+        } else if ch == '\t' {
+            self.end.column = next_tab_stop(self.end.column, self.tab_width);
+        } else {
+            self.end.column += 1;
+        }
+    }
+}
+
+// Scans characters from a reader, reading and decoding them only as they are
+// needed, so large inputs never have to be held in memory at once. Only the
+// `window` characters before the furthest one read are kept for
+// backtracking. Going back further than that makes the scanner act as if the
+// input had ended, and the error explaining why can then be taken with
+// `take_rewind_error`. Likewise a read error ends the input, and can then be
+// taken with `take_error`. Tokens and positions are as in `StringScanner`.
+pub struct ReadScanner<R: Read> {
+    source: Rc<RefCell<Source<R>>>,
+    index: usize
+}
+
+impl<R: Read> ReadScanner<R> {
+    pub fn new(reader: R) -> Self {
+        ReadScanner::with_window(reader, 4096)
+    }
+
+    pub fn with_window(reader: R, window: usize) -> Self {
+        ReadScanner {
+            source: Rc::new(RefCell::new(Source {
+                reader,
+                pending: vec![],
+                chars: VecDeque::new(),
+                start: 0,
+                window: window.max(1),
+                end: Position::new(0, 1, 1),
+                tab_width: 1,
+                finished: false,
+                error: None,
+                rewind_error: None
+            })),
+            index: 0
+        }
+    }
+
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.source.borrow_mut().error.take()
+    }
+
+    pub fn take_rewind_error(&self) -> Option<ParseError> {
+        self.source.borrow_mut().rewind_error.take()
+    }
+
+    fn token_at(&self, index: usize) -> Rc<CharToken> {
+        let mut source = self.source.borrow_mut();
+        match source.get(index) {
+            Some(token) => token,
+            None => Rc::new(CharToken { ch: '\0', position: source.end })
+        }
+    }
+}
+
+// Characters decoded before the tab width is set keep the columns they had
+impl<R: Read> TabStops for ReadScanner<R> {
+    fn set_tab_width(self, tab_width: usize) -> Self {
+        self.source.borrow_mut().tab_width = tab_width.max(1);
+        self
+    }
+}
+
+impl<R: Read> Scanner for ReadScanner<R> {
+    type Token = CharToken;
+    type Position = Position;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        ReadScanner {
+            source: Rc::clone(&scanner.source),
+            index: scanner.index
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self> {
+        Checkpoint::new(self.index)
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self {
+        self.index = checkpoint.into_state();
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        if !self.is_finished() {
+            self.index += 1;
+        }
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.source.borrow_mut().get(self.index).is_none()
+    }
+
+    fn current_token(&self) -> Rc<CharToken> {
+        match self.index {
+            0 => Rc::new(CharToken { ch: '\0', position: Position::new(0, 1, 1) }),
+            index => self.token_at(index - 1)
+        }
+    }
+
+    fn next_token(&self) -> Rc<CharToken> {
+        self.token_at(self.index)
+    }
+
+    fn position(&self) -> Position {
+        self.next_token().position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::basic_functions::{consume, take_while};
+    use std::io::Cursor;
+
+    // Hands out one byte per read, splitting multi-byte characters
+    struct Trickle<R>(R);
+
+    impl<R: Read> Read for Trickle<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn scan_test() {
+        let scanner = ReadScanner::new(Cursor::new("a\né"));
+        assert_eq!('\0', scanner.current_token().ch);
+        assert_eq!('a', scanner.next_token().ch);
+
+        let scanner = scanner.scan_token().scan_token();
+        assert_eq!('\n', scanner.current_token().ch);
+        assert_eq!(Position::new(2, 2, 1), scanner.position());
+
+        let scanner = scanner.scan_token();
+        assert_eq!('é', scanner.current_token().ch);
+        assert!(scanner.is_finished());
+        assert_eq!(Position::new(4, 2, 2), scanner.position());
+
+        let scanner = ReadScanner::new(Trickle(Cursor::new("é€x")));
+        let letters = take_while(|token: &CharToken| token.ch != 'x')
+            .map(|tokens: Vec<Rc<CharToken>>| tokens.iter().map(|token| token.ch).collect::<String>());
+        assert_eq!(Ok("é€".to_string()), letters.run(scanner));
+
+        let scanner = ReadScanner::new(Cursor::new(vec![b'a', 0xff, b'b']));
+        let scanner = scanner.scan_token().scan_token();
+        assert_eq!('\u{FFFD}', scanner.current_token().ch);
+        assert_eq!('b', scanner.next_token().ch);
+    }
+
+    #[test]
+    fn parse_test() {
+        let parser = consume('a', "expected a".to_string()) >> |_|
+                     consume('b', "expected b".to_string()).or_else(consume('c', "expected c".to_string()));
+        assert_eq!(Ok('c'), parser.map(|token: Rc<CharToken>| token.ch).run(ReadScanner::with_window(Cursor::new("ac"), 2)));
+    }
+
+    #[test]
+    fn window_test() {
+        // Going back as far as the window allows
+        let scanner = ReadScanner::with_window(Cursor::new("abcdef"), 2).scan_token().scan_token();
+        let checkpoint = scanner.checkpoint();
+        let scanner = scanner.scan_token().scan_token();
+        assert_eq!('e', scanner.next_token().ch);
+        let scanner = scanner.restore(checkpoint);
+        assert_eq!('b', scanner.current_token().ch);
+        assert_eq!('c', scanner.next_token().ch);
+        assert!(scanner.take_rewind_error().is_none());
+
+        // Going back further ends the input instead
+        let scanner = ReadScanner::with_window(Cursor::new("abcdef"), 2);
+        let checkpoint = scanner.checkpoint();
+        let scanner = scanner.scan_token().scan_token().scan_token().scan_token();
+        assert_eq!('e', scanner.next_token().ch);
+        let scanner = scanner.restore(checkpoint);
+        assert!(scanner.is_finished());
+        assert_eq!(Some(ParseError::new("cannot go back more than 2 characters").at(Position::new(1, 1, 2))),
+                   scanner.take_rewind_error());
+
+        // The window doesn't depend on how much is read at once
+        let scanner = ReadScanner::with_window(Cursor::new("a".repeat(2 * CHUNK_SIZE)), 2);
+        assert_eq!('\0', scanner.current_token().ch);
+        assert_eq!(Position::new(0, 1, 1), scanner.position());
+    }
+
+    #[test]
+    fn rewind_error_test() {
+        let scanner = ReadScanner::with_window(Cursor::new("abcde"), 2);
+        let copy = ReadScanner::from_scanner(&scanner);
+        let parser = (take_while(|token: &CharToken| token.ch != 'e') >> |_|
+                      consume('x', "expected x".to_string()))
+                     .or_else(consume('a', "expected a".to_string()));
+        assert!(parser.run(scanner).is_err());
+        assert_eq!(Some(ParseError::new("cannot go back more than 2 characters").at(Position::new(1, 1, 2))),
+                   copy.take_rewind_error());
+    }
+}
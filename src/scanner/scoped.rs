@@ -0,0 +1,107 @@
+use crate::scanner::{Checkpoint, Scanner};
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Scanners that carry a stack of scopes mapping declared names to
+// information about them, for resolving names while parsing
+pub trait Scopes: Scanner {
+    type Info: Clone;
+    fn push_scope(self) -> Self;
+    fn pop_scope(self) -> Self;
+    fn declare(self, name: String, info: Self::Info) -> Self;
+    // The innermost declaration of `name`, if any
+    fn lookup(&self, name: &str) -> Option<Self::Info>;
+}
+
+// Wraps a scanner with a scope stack. The outermost scope is never popped.
+pub struct ScopedScanner<S: Scanner, I> {
+    scanner: S,
+    scopes: Vec<HashMap<String, I>>
+}
+
+impl<S: Scanner, I> ScopedScanner<S, I> {
+    pub fn new(scanner: S) -> Self {
+        ScopedScanner {
+            scanner,
+            scopes: vec![HashMap::new()]
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.scanner
+    }
+}
+
+impl<S: Scanner, I: Clone> Scanner for ScopedScanner<S, I> {
+    type Token = S::Token;
+    type Position = S::Position;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        ScopedScanner {
+            scanner: S::from_scanner(&scanner.scanner),
+            scopes: scanner.scopes.clone()
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self>
+    where Self: 'static {
+        Checkpoint::new((self.scanner.checkpoint(), self.scopes.clone()))
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self
+    where Self: 'static {
+        let (checkpoint, scopes): (Checkpoint<S>, Vec<HashMap<String, I>>) = checkpoint.into_state();
+        self.scanner = self.scanner.restore(checkpoint);
+        self.scopes = scopes;
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        self.scanner = self.scanner.scan_token();
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.scanner.is_finished()
+    }
+
+    fn current_token(&self) -> Rc<Self::Token> {
+        self.scanner.current_token()
+    }
+
+    fn next_token(&self) -> Rc<Self::Token> {
+        self.scanner.next_token()
+    }
+
+    fn position(&self) -> Self::Position {
+        self.scanner.position()
+    }
+}
+
+impl<S: Scanner, I: Clone> Scopes for ScopedScanner<S, I> {
+    type Info = I;
+
+    fn push_scope(mut self) -> Self {
+        self.scopes.push(HashMap::new());
+        self
+    }
+
+    fn pop_scope(mut self) -> Self {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+        self
+    }
+
+    fn declare(mut self, name: String, info: I) -> Self {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, info);
+        }
+        self
+    }
+
+    fn lookup(&self, name: &str) -> Option<I> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).cloned())
+    }
+}
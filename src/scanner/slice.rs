@@ -0,0 +1,194 @@
+use crate::scanner::{Checkpoint, Scanner, Token};
+
+use std::rc::Rc;
+
+// Scans over tokens produced up front by a lexer. `end` is reported as the
+// current token before anything is consumed and as the next token once the
+// tokens run out. Copies share the tokens, so snapshots are cheap, and each
+// token is kept in its own `Rc`, so reading one never copies it.
+pub struct SliceScanner<T: Token + Clone> {
+    tokens: Rc<[Rc<T>]>,
+    end: Rc<T>,
+    index: usize
+}
+
+impl<T: Token + Clone> SliceScanner<T> {
+    pub fn new(tokens: Vec<T>, end: T) -> Self {
+        SliceScanner {
+            tokens: tokens.into_iter().map(Rc::new).collect(),
+            end: Rc::new(end),
+            index: 0
+        }
+    }
+
+    // The tokens not yet consumed, and the token reported after them
+    pub(crate) fn rest(&self) -> (Vec<T>, T) {
+        let tokens = self.tokens[self.index..].iter().map(|token| (**token).clone()).collect();
+        (tokens, (*self.end).clone())
+    }
+
+    // Moves to just before the token at `index`, as if that many tokens had
+    // been consumed
+    pub(crate) fn seek(mut self, index: usize) -> Self {
+        self.index = index.min(self.tokens.len());
+        self
+    }
+
+    fn token_at(&self, index: usize) -> Rc<T> {
+        match self.tokens.get(index) {
+            Some(token) => Rc::clone(token),
+            None => Rc::clone(&self.end)
+        }
+    }
+}
+
+impl<T: Token + Clone> Scanner for SliceScanner<T> {
+    type Token = T;
+    type Position = usize;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        SliceScanner {
+            tokens: Rc::clone(&scanner.tokens),
+            end: Rc::clone(&scanner.end),
+            index: scanner.index
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self> {
+        Checkpoint::new(self.index)
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self {
+        self.index = checkpoint.into_state();
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        if !self.is_finished() {
+            self.index += 1;
+        }
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.index >= self.tokens.len()
+    }
+
+    fn current_token(&self) -> Rc<T> {
+        match self.index {
+            0 => Rc::clone(&self.end),
+            index => self.token_at(index - 1)
+        }
+    }
+
+    fn next_token(&self) -> Rc<T> {
+        self.token_at(self.index)
+    }
+
+    fn position(&self) -> usize {
+        self.index
+    }
+
+    fn peek_n(&self, n: usize) -> Rc<T> {
+        match n {
+            0 => self.current_token(),
+            n => self.token_at(self.index + n - 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::parser::basic_functions::{advance, is_at_end, peek, previous};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum TestToken {
+        A,
+        B,
+        End
+    }
+
+    impl Token for TestToken {
+        type TokenType = TestToken;
+        fn t_type(&self) -> TestToken {
+            self.clone()
+        }
+    }
+
+    fn scanner(tokens: Vec<TestToken>) -> SliceScanner<TestToken> {
+        SliceScanner::new(tokens, TestToken::End)
+    }
+
+    #[test]
+    fn advance_test() {
+        let next_token = scanner(vec![TestToken::A]).scan_token().current_token();
+        assert_eq!(Ok(next_token), advance().run(scanner(vec![TestToken::A])));
+
+        // Ensure that advance actually advances the scanner
+        assert!((
+            previous() >> |a|
+            peek() >> |b|
+            advance() >> |_|
+            previous() >> move |c| {
+                assert_ne!(a, c);
+                assert_eq!(b, c);
+                Parser::result(())
+            }
+        ).run(scanner(vec![TestToken::A])).is_ok());
+    }
+
+    #[test]
+    fn end_test() {
+        let scanner = scanner(vec![TestToken::A, TestToken::B]);
+        assert_eq!(TestToken::End, *scanner.current_token());
+        assert_eq!(Ok((TestToken::B, TestToken::End, true)),
+            (advance() >> |_|
+            advance() >> |b: Rc<TestToken>|
+            peek() >> move |end: Rc<TestToken>|
+            is_at_end() >> move |at_end|
+            Parser::result(((*b).clone(), (*end).clone(), at_end))).run(scanner));
+    }
+
+    #[test]
+    fn from_scanner_test() {
+        let original = scanner(vec![TestToken::A, TestToken::B]).scan_token();
+        let copy = SliceScanner::from_scanner(&original);
+        assert!(Rc::ptr_eq(&original.tokens, &copy.tokens));
+        assert_eq!(original.position(), copy.position());
+
+        let copy = copy.scan_token();
+        assert_eq!(1, original.position());
+        assert_eq!(2, copy.position());
+
+        // Both hand out the same token rather than copies of it
+        assert!(Rc::ptr_eq(&original.next_token(), &copy.current_token()));
+        assert!(Rc::ptr_eq(&original.next_token(), &original.peek_n(1)));
+    }
+
+    #[test]
+    fn peek_n_test() {
+        let scanner = scanner(vec![TestToken::A, TestToken::B, TestToken::A]);
+        assert_eq!(TestToken::B, *scanner.peek_n(2));
+        assert_eq!(TestToken::End, *scanner.peek_n(4));
+        assert_eq!(TestToken::End, *scanner.peek_n(10));
+
+        let scanner = scanner.scan_token();
+        assert_eq!(TestToken::A, *scanner.peek_n(0));
+        assert_eq!(TestToken::B, *scanner.peek_n(1));
+    }
+
+    #[test]
+    fn checkpoint_test() {
+        let scanner = scanner(vec![TestToken::A, TestToken::B]).scan_token();
+        let checkpoint = scanner.checkpoint();
+        let scanner = scanner.scan_token();
+        assert_eq!(2, scanner.position());
+
+        let scanner = scanner.restore(checkpoint);
+        assert_eq!(1, scanner.position());
+        assert_eq!(TestToken::A, *scanner.current_token());
+        assert_eq!(TestToken::B, *scanner.next_token());
+    }
+}
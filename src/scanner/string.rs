@@ -0,0 +1,226 @@
+use crate::error::Position;
+use crate::scanner::{Checkpoint, Scanner, Token, Trivia};
+
+use std::rc::Rc;
+
+// A single character of the source. Before the first character and after the
+// last, the scanner reports a `'\0'` token at the current position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharToken {
+    pub ch: char,
+    pub position: Position
+}
+
+impl Token for CharToken {
+    type TokenType = char;
+    fn t_type(&self) -> char {
+        self.ch
+    }
+}
+
+impl Trivia for CharToken {
+    fn is_trivia(&self) -> bool {
+        self.ch.is_whitespace()
+    }
+
+    fn is_line_break(&self) -> bool {
+        self.ch == '\n'
+    }
+}
+
+// Character scanners whose columns count a tab as reaching the next multiple
+// of a tab width, so that the columns of positions can agree with a layout
+// that expands tabs the same way
+pub trait TabStops: Scanner<Token = CharToken> {
+    fn set_tab_width(self, tab_width: usize) -> Self;
+}
+
+// The column after a character at `column` that is a tab
+pub(crate) fn next_tab_stop(column: usize, tab_width: usize) -> usize {
+    (column - 1) / tab_width * tab_width + tab_width + 1
+}
+
+// Scans a string one character at a time, tracking the byte offset, line and
+// column of each character. A tab counts as a single column unless the tab
+// width is set with `with_tab_width`.
+pub struct StringScanner {
+    source: Rc<str>,
+    position: Position,
+    current: Rc<CharToken>,
+    tab_width: usize
+}
+
+impl StringScanner {
+    pub fn new(source: &str) -> Self {
+        StringScanner::with_tab_width(source, 1)
+    }
+
+    pub fn with_tab_width(source: &str, tab_width: usize) -> Self {
+        let position = Position::new(0, 1, 1);
+        StringScanner {
+            source: Rc::from(source),
+            position,
+            current: Rc::new(CharToken { ch: '\0', position }),
+            tab_width: tab_width.max(1)
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.source[self.position.offset..].chars().next()
+    }
+}
+
+impl Scanner for StringScanner {
+    type Token = CharToken;
+    type Position = Position;
+
+    fn from_scanner(scanner: &Self) -> Self {
+        StringScanner {
+            source: Rc::clone(&scanner.source),
+            position: scanner.position,
+            current: Rc::clone(&scanner.current),
+            tab_width: scanner.tab_width
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint<Self> {
+        Checkpoint::new((self.position, Rc::clone(&self.current)))
+    }
+
+    fn restore(mut self, checkpoint: Checkpoint<Self>) -> Self {
+        let (position, current) = checkpoint.into_state();
+        self.position = position;
+        self.current = current;
+        self
+    }
+
+    fn scan_token(mut self) -> Self {
+        if let Some(ch) = self.peek_char() {
+            self.current = Rc::new(CharToken { ch, position: self.position });
+            self.position.offset += ch.len_utf8();
+            if ch == '\n' {
+                self.position.line += 1;
+                self.position.column = 1;
+            } else if ch == '\t' {
+                self.position.column = next_tab_stop(self.position.column, self.tab_width);
+            } else {
+                self.position.column += 1;
+            }
+        } else {
+            self.current = Rc::new(CharToken { ch: '\0', position: self.position });
+        }
+        self
+    }
+
+    fn is_finished(&self) -> bool {
+        self.position.offset >= self.source.len()
+    }
+
+    fn current_token(&self) -> Rc<Self::Token> {
+        Rc::clone(&self.current)
+    }
+
+    fn next_token(&self) -> Rc<Self::Token> {
+        Rc::new(CharToken {
+            ch: self.peek_char().unwrap_or('\0'),
+            position: self.position
+        })
+    }
+
+    fn position(&self) -> Position {
+        self.position
+    }
+}
+
+impl TabStops for StringScanner {
+    fn set_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ParseError, ParseErrors};
+    use crate::parser::Parser;
+    use crate::parser::basic_functions::{consume, matches};
+
+    #[test]
+    fn scan_test() {
+        let scanner = StringScanner::new("abc");
+        assert!(!scanner.is_finished());
+        assert_eq!('\0', scanner.current_token().ch);
+        assert_eq!('a', scanner.next_token().ch);
+
+        let scanner = scanner.scan_token();
+        assert_eq!('a', scanner.current_token().ch);
+        assert_eq!('b', scanner.next_token().ch);
+
+        let scanner = scanner.scan_token().scan_token();
+        assert_eq!('c', scanner.current_token().ch);
+        assert_eq!(Position::new(2, 1, 3), scanner.current_token().position);
+        assert_eq!('\0', scanner.next_token().ch);
+        assert!(scanner.is_finished());
+
+        let scanner = scanner.scan_token();
+        assert_eq!('\0', scanner.current_token().ch);
+        assert_eq!(Position::new(3, 1, 4), scanner.position());
+    }
+
+    #[test]
+    fn position_test() {
+        let scanner = StringScanner::new("a\né");
+        let scanner = scanner.scan_token().scan_token();
+        assert_eq!(Position::new(1, 1, 2), scanner.current_token().position);
+        assert_eq!(Position::new(2, 2, 1), scanner.position());
+
+        let scanner = scanner.scan_token();
+        assert_eq!('é', scanner.current_token().ch);
+        assert_eq!(Position::new(4, 2, 2), scanner.position());
+        assert!(scanner.is_finished());
+    }
+
+    #[test]
+    fn tab_width_test() {
+        // A tab reaches the next tab stop, even after other characters
+        let columns = |scanner: StringScanner| {
+            let mut scanner = scanner;
+            let mut columns = vec![];
+            while !scanner.is_finished() {
+                scanner = scanner.scan_token();
+                columns.push(scanner.position().column);
+            }
+            columns
+        };
+        assert_eq!(vec![2, 3, 4, 5], columns(StringScanner::new("\ta\tb")));
+        assert_eq!(vec![5, 6, 9, 10], columns(StringScanner::with_tab_width("\ta\tb", 4)));
+        assert_eq!(vec![9, 10, 17, 18], columns(StringScanner::new("\ta\tb").set_tab_width(8)));
+    }
+
+    #[test]
+    fn checkpoint_test() {
+        let scanner = StringScanner::new("a\nb").scan_token();
+        let checkpoint = scanner.checkpoint();
+        let scanner = scanner.scan_token().scan_token();
+        assert_eq!(Position::new(3, 2, 2), scanner.position());
+
+        let scanner = scanner.restore(checkpoint);
+        assert_eq!(Position::new(1, 1, 2), scanner.position());
+        assert_eq!('a', scanner.current_token().ch);
+        assert_eq!('\n', scanner.next_token().ch);
+    }
+
+    #[test]
+    fn parse_test() {
+        let parser = matches('a') >> |a|
+                     matches('x') >> move |x|
+                     consume('b', "expected b".to_string()) >> move |b: Rc<CharToken>|
+                     Parser::result((a, x, b.ch));
+        assert_eq!(Ok((true, false, 'b')), parser.run(StringScanner::new("abc")));
+
+        let parser = consume('b', "expected b".to_string());
+        assert_eq!(Err(ParseErrors(vec![ParseError::new("expected b").at(Position::new(0, 1, 1))])),
+                   parser.run(StringScanner::new("abc")));
+    }
+}
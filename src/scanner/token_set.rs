@@ -0,0 +1,56 @@
+use crate::scanner::TokenTypeIndex;
+
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+
+// A set of token types stored as a bitset indexed by `TokenTypeIndex`
+pub struct TokenSet<T: TokenTypeIndex> {
+    bits: Vec<u64>,
+    t_type: PhantomData<T>
+}
+
+impl<T: TokenTypeIndex> TokenSet<T> {
+    pub fn new() -> Self {
+        TokenSet {
+            bits: vec![0; T::COUNT.div_ceil(64)],
+            t_type: PhantomData
+        }
+    }
+
+    // `t_type.index()` must be below `T::COUNT`, as `TokenTypeIndex` requires
+    pub fn insert(&mut self, t_type: &T) {
+        let index = t_type.index();
+        debug_assert!(index < T::COUNT, "token type index {} is not below COUNT ({})", index, T::COUNT);
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn contains(&self, t_type: &T) -> bool {
+        let index = t_type.index();
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
+impl<T: TokenTypeIndex> Default for TokenSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: TokenTypeIndex> Clone for TokenSet<T> {
+    fn clone(&self) -> Self {
+        TokenSet {
+            bits: self.bits.clone(),
+            t_type: PhantomData
+        }
+    }
+}
+
+impl<T: TokenTypeIndex> FromIterator<T> for TokenSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = TokenSet::new();
+        for t_type in iter {
+            set.insert(&t_type);
+        }
+        set
+    }
+}